@@ -1,5 +1,6 @@
 use once_cell::sync::Lazy;
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 
 pub type ZobristKey = u64;
 
@@ -10,8 +11,12 @@ struct ZobristTables {
     white_to_move: u64,
 }
 
+// Fixed seed: table indices derive from keys, so node counts are only
+// reproducible across runs when the keys are.
+const ZOBRIST_SEED: u64 = 0x4b61_6973_7361;
+
 static ZOBRIST: Lazy<ZobristTables> = Lazy::new(|| {
-    let mut rng = rand::thread_rng();
+    let mut rng = StdRng::seed_from_u64(ZOBRIST_SEED);
     let mut piece = [[0u64; 64]; 13];
     let mut castling = [0u64; 4];
     let mut en_passant = [0u64; 64];
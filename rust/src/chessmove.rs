@@ -11,6 +11,21 @@ pub struct ChessMove {
     pub is_castle: bool,
 }
 
+impl ChessMove {
+    /// A move that neither captures, promotes nor castles, for tests.
+    #[cfg(test)]
+    pub fn quiet(from: i32, to: i32) -> ChessMove {
+        ChessMove {
+            from,
+            to,
+            promoted_piece: Piece::Empty,
+            captured_piece: Piece::Empty,
+            is_en_passant: false,
+            is_castle: false,
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct UndoInfo {
     pub the_move: ChessMove,
@@ -3,27 +3,81 @@ use std::sync::{
     Arc, Mutex,
 };
 use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use crate::board::Board;
+use crate::board::{Board, ZobristKey};
 use crate::chessmove::ChessMove;
 use crate::params::{GoParams, PositionParams};
-use crate::position_utils::set_board_position;
-use crate::search::move_to_uci;
-use crate::search::Search;
+use crate::position_utils::{parse_uci_move, set_board_position, set_board_position_with_history};
+use crate::search::{Search, SearchLimits, SearchObserver, SearchResult, MAX_DEPTH};
+use crate::skill::{next_random, Skill, MAX_ELO, MAX_SKILL_LEVEL, MIN_ELO};
+use crate::timeman::TimeManager;
+use crate::trace::SearchTrace;
+use crate::uci::UciObserver;
+
+pub const DEFAULT_MOVE_OVERHEAD_MS: u64 = 10;
+pub const DEFAULT_BENCH_DEPTH: i32 = 8;
+pub const MAX_CONTEMPT_CP: i32 = 100;
+// Each thread has its own move ordering tables, a few megabytes.
+pub const MAX_THREADS: usize = 256;
+// `debug on` traces this many plies of each search into the trace file.
+pub const DEFAULT_TRACE_DEPTH: usize = 3;
+pub const MAX_TRACE_DEPTH: usize = 16;
+pub const DEFAULT_TRACE_FILE: &str = "kaissa_trace.dot";
+
+// Openings, middlegames with tactics and endgames, searched by `bench`.
+const BENCH_POSITIONS: [&str; 8] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4",
+    "r1bqkbnr/pppp1ppp/2n5/4p3/2B1P3/5Q2/PPPP1PPP/RNB1K1NR b KQkq - 3 3",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "r2qkb1r/pp2nppp/3p4/2pNN1B1/2BnP3/3P4/PPP2PPP/R2bK2R w KQkq - 1 1",
+    "r1b3kr/ppp1Bp1p/1b6/n2P4/2p3q1/2Q2N2/P4PPP/RN2R1K1 w - - 1 1",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    "4k3/8/8/3p4/3P4/8/8/4K3 w - - 0 1",
+];
 
 pub struct Engine {
     board: Board,
-    search: Search,
+    // Keys of the positions before `board` in the current game.
+    history: Vec<ZobristKey>,
+    // Shared with the worker thread so the table and ordering heuristics
+    // survive from one move to the next.
+    search: Arc<Mutex<Search>>,
 
     stop_requested: Arc<AtomicBool>,
+    pondering: Arc<AtomicBool>,
 
     search_thread: Option<JoinHandle<()>>,
 
-    best_move: Arc<Mutex<Option<ChessMove>>>,
+    observer: Arc<dyn SearchObserver>,
+    last_result: Arc<Mutex<Option<SearchResult>>>,
+
+    move_overhead: Duration,
+    // Centipawns; ignored in analysis mode, where draws are just draws.
+    contempt: i32,
+    analyse_mode: bool,
+    // Skill Level, unless UCI_LimitStrength asks for UCI_Elo instead.
+    skill_level: i32,
+    limit_strength: bool,
+    elo: i32,
+    // Seeds each weakened search's choice of move.
+    rng_state: u64,
+    // `debug on`: search diagnostics and a trace of every search.
+    debug: bool,
+    trace_depth: usize,
+    trace_file: String,
 }
 
 impl Engine {
     pub fn new() -> Self {
+        Engine::with_observer(Arc::new(UciObserver))
+    }
+
+    /// An engine reporting its searches to `observer` instead of stdout.
+    pub fn with_observer(observer: Arc<dyn SearchObserver>) -> Self {
+        let mut search = Search::new();
+        search.set_observer(Arc::clone(&observer));
         Engine {
             board: Board {
                 board: [crate::board::Piece::Empty; 64],
@@ -36,44 +90,298 @@ impl Engine {
                 half_move_capture_or_pawn_clock: 0,
                 full_move_number: 1,
             },
-            search: Search::new(),
+            history: Vec::new(),
+            search: Arc::new(Mutex::new(search)),
             stop_requested: Arc::new(AtomicBool::new(false)),
+            pondering: Arc::new(AtomicBool::new(false)),
             search_thread: None,
-            best_move: Arc::new(Mutex::new(None)),
+            observer,
+            last_result: Arc::new(Mutex::new(None)),
+            move_overhead: Duration::from_millis(DEFAULT_MOVE_OVERHEAD_MS),
+            contempt: 0,
+            analyse_mode: false,
+            skill_level: MAX_SKILL_LEVEL,
+            limit_strength: false,
+            elo: MAX_ELO,
+            rng_state: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_nanos() as u64),
+            debug: false,
+            trace_depth: DEFAULT_TRACE_DEPTH,
+            trace_file: DEFAULT_TRACE_FILE.to_string(),
         }
     }
 
     pub fn set_position(&mut self, params: &PositionParams) {
-        set_board_position(&mut self.board, params).expect("Invalid position command");
+        self.history = set_board_position_with_history(&mut self.board, params)
+            .expect("Invalid position command");
+    }
+
+    pub fn new_game(&mut self) {
+        self.stop();
+        self.search.lock().unwrap().clear();
+    }
+
+    pub fn set_option(&mut self, name: &str, value: &str) -> Result<(), String> {
+        // Options are only sent while idle; make sure the search is unlocked.
+        self.stop();
+        match name.to_ascii_lowercase().as_str() {
+            "multipv" => {
+                let multi_pv = value
+                    .parse::<usize>()
+                    .map_err(|e| format!("Invalid MultiPV value '{}': {}", value, e))?;
+                self.search.lock().unwrap().set_multi_pv(multi_pv);
+            }
+            "threads" => {
+                let threads = value
+                    .parse::<usize>()
+                    .map_err(|e| format!("Invalid Threads value '{}': {}", value, e))?;
+                self.search
+                    .lock()
+                    .unwrap()
+                    .set_threads(threads.clamp(1, MAX_THREADS));
+            }
+            "hash" => {
+                let size_mb = value
+                    .parse::<usize>()
+                    .map_err(|e| format!("Invalid Hash value '{}': {}", value, e))?;
+                self.search.lock().unwrap().set_hash_size(size_mb);
+            }
+            "clear hash" => self.search.lock().unwrap().clear(),
+            // Only tells the GUI it may send `go ponder`; nothing to store.
+            "ponder" => {}
+            "move overhead" => {
+                let ms = value
+                    .parse::<u64>()
+                    .map_err(|e| format!("Invalid Move Overhead value '{}': {}", value, e))?;
+                self.move_overhead = Duration::from_millis(ms);
+            }
+            "contempt" => {
+                let contempt = value
+                    .parse::<i32>()
+                    .map_err(|e| format!("Invalid Contempt value '{}': {}", value, e))?;
+                self.contempt = contempt.clamp(-MAX_CONTEMPT_CP, MAX_CONTEMPT_CP);
+            }
+            "skill level" => {
+                let level = value
+                    .parse::<i32>()
+                    .map_err(|e| format!("Invalid Skill Level value '{}': {}", value, e))?;
+                self.skill_level = level.clamp(0, MAX_SKILL_LEVEL);
+            }
+            "uci_limitstrength" => {
+                self.limit_strength = value
+                    .parse::<bool>()
+                    .map_err(|e| format!("Invalid UCI_LimitStrength value '{}': {}", value, e))?;
+            }
+            "uci_elo" => {
+                let elo = value
+                    .parse::<i32>()
+                    .map_err(|e| format!("Invalid UCI_Elo value '{}': {}", value, e))?;
+                self.elo = elo.clamp(MIN_ELO, MAX_ELO);
+            }
+            "trace depth" => {
+                let depth = value
+                    .parse::<usize>()
+                    .map_err(|e| format!("Invalid Trace Depth value '{}': {}", value, e))?;
+                self.trace_depth = depth.clamp(1, MAX_TRACE_DEPTH);
+            }
+            "trace file" => self.trace_file = value.to_string(),
+            "uci_analysemode" => {
+                self.analyse_mode = value
+                    .parse::<bool>()
+                    .map_err(|e| format!("Invalid UCI_AnalyseMode value '{}': {}", value, e))?;
+            }
+            _ => self.search.lock().unwrap().set_param(name, value)?,
+        }
+        Ok(())
+    }
+
+    /// With debug on, every search traces its tree into the trace file.
+    /// UCI allows `debug` during a search, which carries on as it started.
+    pub fn set_debug(&mut self, on: bool) {
+        self.debug = on;
     }
 
     pub fn go(&mut self, params: &GoParams) {
         self.stop();
         self.stop_requested.store(false, Ordering::Relaxed);
 
+        let ponder = params.ponder.unwrap_or(false);
+        self.pondering.store(ponder, Ordering::Relaxed);
+        self.search
+            .lock()
+            .unwrap()
+            .set_trace_depth(self.debug.then_some(self.trace_depth));
+
         let board_clone = self.board.clone();
         let stop_clone = Arc::clone(&self.stop_requested);
-        let best_move_clone = Arc::clone(&self.best_move);
-        let depth = params.depth.unwrap_or(5);
-        let movetime = params.movetime;
+        let pondering_clone = Arc::clone(&self.pondering);
+        let observer = Arc::clone(&self.observer);
+        let last_result = Arc::clone(&self.last_result);
+        let infinite = params.infinite;
+        let trace_file = self.trace_file.clone();
+        let debug = self.debug;
 
-        let mut search_obj = self.search.clone();
+        // Infinite analysis ignores every other limit and runs until stop.
+        let time_manager = if infinite {
+            None
+        } else {
+            self.time_manager(params)
+        };
+        // Any node or time budget should bound the search, not the default depth.
+        let default_depth = if infinite
+            || params.nodes.is_some()
+            || params.movetime.is_some()
+            || time_manager.is_some()
+        {
+            MAX_DEPTH as i32 - 1
+        } else {
+            5
+        };
+        let mut depth = params.depth.filter(|_| !infinite).unwrap_or(default_depth);
+        let mut nodes = params.nodes.filter(|_| !infinite);
+        let level = if self.limit_strength {
+            Skill::level_for_elo(self.elo)
+        } else {
+            self.skill_level
+        };
+        let skill = Skill::from_level(level, next_random(&mut self.rng_state));
+        if let Some(skill) = &skill {
+            depth = depth.min(skill.depth_limit());
+            nodes = Some(nodes.map_or(skill.node_limit(), |n| n.min(skill.node_limit())));
+        }
+        let limits = SearchLimits {
+            depth,
+            move_time: params.movetime.filter(|_| !infinite),
+            nodes,
+            mate: params.mate,
+            time_manager,
+            ponder: ponder.then(|| Arc::clone(&self.pondering)),
+            search_moves: self.parse_search_moves(&params.searchmoves),
+            history: self.history.clone(),
+            // Infinite searches are analysis too.
+            contempt: if self.analyse_mode || infinite {
+                0
+            } else {
+                self.contempt
+            },
+            skill,
+        };
+
+        let search_clone = Arc::clone(&self.search);
 
         self.search_thread = Some(thread::spawn(move || {
-            let result =
-                search_obj.find_best_move(&mut board_clone.clone(), depth, &stop_clone, movetime);
-            if let Some(m) = result {
-                {
-                    let mut locked = best_move_clone.lock().unwrap();
-                    *locked = Some(m);
+            let result = search_clone.lock().unwrap().find_best_move(
+                &mut board_clone.clone(),
+                &limits,
+                &stop_clone,
+            );
+
+            // UCI forbids sending bestmove for infinite or ponder searches
+            // before the GUI sends stop (or ponderhit, for ponder).
+            while (infinite || pondering_clone.load(Ordering::Relaxed))
+                && !stop_clone.load(Ordering::Relaxed)
+            {
+                thread::sleep(Duration::from_millis(1));
+            }
+
+            if debug {
+                if let Some(rate) = result.stats.first_move_cutoff_rate() {
+                    observer.on_message(&format!(
+                        "first move cutoffs {:.1}% of {}",
+                        rate * 100.0,
+                        result.stats.cutoffs
+                    ));
+                }
+                if let Some(reason) = result.stats.early_stop {
+                    observer.on_message(&format!("stopped early: {}", reason));
                 }
-                println!("bestmove {}", move_to_uci(&m));
-            } else {
-                println!("bestmove 0000");
             }
+            if let Some(trace) = &result.trace {
+                observer.on_message(&match write_trace(trace, &trace_file) {
+                    Ok(()) => format!(
+                        "trace of {} nodes at depth {} written to {}",
+                        trace.nodes.len(),
+                        trace.depth,
+                        trace_file
+                    ),
+                    Err(e) => e,
+                });
+            }
+            observer.on_result(&result);
+            *last_result.lock().unwrap() = Some(result);
         }));
     }
 
+    /// Searches every bench position to `depth` from a cleared state.
+    /// Returns the total node count, a signature of the search's
+    /// behaviour when single-threaded, and the time taken. Clears the
+    /// hash table.
+    pub fn bench(&mut self, depth: i32) -> (u64, Duration) {
+        self.stop();
+        self.stop_requested.store(false, Ordering::Relaxed);
+        let mut search = self.search.lock().unwrap();
+        let limits = SearchLimits::with_depth(depth);
+        let start = Instant::now();
+        let mut nodes = 0;
+
+        for (i, fen) in BENCH_POSITIONS.iter().enumerate() {
+            let params = PositionParams {
+                is_fen: true,
+                position: fen.to_string(),
+                moves: vec![],
+            };
+            let mut board = self.board.clone();
+            set_board_position(&mut board, &params).expect("Invalid bench position");
+            search.clear();
+            let position_start = Instant::now();
+            let result = search.find_best_move(&mut board, &limits, &self.stop_requested);
+            self.observer.on_message(&format!(
+                "bench position {} nodes {} time {}",
+                i + 1,
+                result.stats.nodes,
+                position_start.elapsed().as_millis()
+            ));
+            nodes += result.stats.nodes;
+        }
+        search.clear();
+        (nodes, start.elapsed())
+    }
+
+    /// The opponent played the expected move: keep searching, but on the clock.
+    pub fn ponder_hit(&mut self) {
+        self.pondering.store(false, Ordering::Relaxed);
+    }
+
+    fn time_manager(&self, params: &GoParams) -> Option<TimeManager> {
+        let (remaining, increment) = if self.board.white_to_move {
+            (params.wtime, params.winc)
+        } else {
+            (params.btime, params.binc)
+        };
+        remaining.map(|remaining| {
+            TimeManager::from_clock(
+                remaining,
+                increment.unwrap_or(Duration::ZERO),
+                params.movestogo,
+                self.move_overhead,
+            )
+        })
+    }
+
+    fn parse_search_moves(&self, search_moves: &[String]) -> Vec<ChessMove> {
+        let mut moves = Vec::with_capacity(search_moves.len());
+        for mv_str in search_moves {
+            match parse_uci_move(&self.board, mv_str) {
+                Ok(mv) => moves.push(mv),
+                Err(e) => self
+                    .observer
+                    .on_message(&format!("Ignoring searchmove: {}", e)),
+            }
+        }
+        moves
+    }
+
     pub fn stop(&mut self) {
         self.stop_requested.store(true, Ordering::Relaxed);
         if let Some(handle) = self.search_thread.take() {
@@ -82,6 +390,21 @@ impl Engine {
     }
 
     pub fn get_best_move(&self) -> Option<ChessMove> {
-        self.best_move.lock().unwrap().clone()
+        self.last_result().and_then(|result| result.best_move)
+    }
+
+    /// The result of the last search to finish.
+    pub fn last_result(&self) -> Option<SearchResult> {
+        self.last_result.lock().unwrap().clone()
     }
 }
+
+/// Writes `trace` as JSON if `path` ends in `.json`, otherwise as Graphviz DOT.
+fn write_trace(trace: &SearchTrace, path: &str) -> Result<(), String> {
+    let contents = if path.ends_with(".json") {
+        trace.to_json()
+    } else {
+        trace.to_dot()
+    };
+    std::fs::write(path, contents).map_err(|e| format!("Cannot write trace to {}: {}", path, e))
+}
@@ -4,13 +4,36 @@ mod board;
 mod chessmove;
 mod engine;
 mod movegen;
+mod ordering;
 mod params;
 mod position_utils;
 mod search;
+mod skill;
+mod timeman;
+mod trace;
+mod tt;
 mod uci;
 
 fn main() {
     let mut engine = engine::Engine::new();
+    // `kaissa_rust bench [depth] [threads]` runs the benchmark and exits.
+    // Comparing the time to depth across thread counts measures scaling.
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("bench") {
+        let depth = args
+            .get(1)
+            .and_then(|d| d.parse().ok())
+            .unwrap_or(engine::DEFAULT_BENCH_DEPTH);
+        if let Some(threads) = args.get(2) {
+            if let Err(e) = engine.set_option("Threads", threads) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        let (nodes, time) = engine.bench(depth);
+        uci::UCI::send_bench_result(nodes, time);
+        return;
+    }
     let mut uci = uci::UCI::new(&mut engine);
     uci.main_loop();
 }
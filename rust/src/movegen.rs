@@ -107,7 +107,8 @@ pub fn make_move(board: &mut Board, mv: &ChessMove) {
     board.board[mv.from as usize] = Piece::Empty;
 
     if mv.is_en_passant {
-        let direction = if moving_piece == Piece::WP { -8 } else { 8 };
+        // The captured pawn sits behind the target square.
+        let direction = if moving_piece == Piece::WP { 8 } else { -8 };
         board.board[(mv.to + direction) as usize] = Piece::Empty;
     }
 
@@ -646,6 +647,104 @@ fn is_square_attacked(board: &Board, square: i32, attacked_by_white: bool) -> bo
     false
 }
 
+// First piece met walking from (row, col) in direction (dr, dc).
+fn first_piece_on_ray(squares: &[Piece; 64], row: i32, col: i32, dr: i32, dc: i32) -> Option<i32> {
+    let (mut r, mut c) = (row + dr, col + dc);
+    while (0..8).contains(&r) && (0..8).contains(&c) {
+        let sq = to_index(r, c);
+        if squares[sq as usize] != Piece::Empty {
+            return Some(sq);
+        }
+        r += dr;
+        c += dc;
+    }
+    None
+}
+
+/// Square of the cheapest piece of the given colour attacking `square` on
+/// `squares`. Pieces behind a slider count once it has been removed, which
+/// is what exchange evaluation needs.
+pub fn least_valuable_attacker(squares: &[Piece; 64], square: i32, white: bool) -> Option<i32> {
+    let (pawn, knight, bishop, rook, queen, king) = if white {
+        (
+            Piece::WP,
+            Piece::WN,
+            Piece::WB,
+            Piece::WR,
+            Piece::WQ,
+            Piece::WK,
+        )
+    } else {
+        (
+            Piece::BP,
+            Piece::BN,
+            Piece::BB,
+            Piece::BR,
+            Piece::BQ,
+            Piece::BK,
+        )
+    };
+    let (row, col) = (row_of(square), col_of(square));
+    let at = |r: i32, c: i32| -> Option<i32> {
+        ((0..8).contains(&r) && (0..8).contains(&c)).then(|| to_index(r, c))
+    };
+    let holding = |piece: Piece| move |&sq: &i32| squares[sq as usize] == piece;
+
+    // White pawns capture towards row 0.
+    let pawn_row = if white { row + 1 } else { row - 1 };
+    let pawns = [at(pawn_row, col - 1), at(pawn_row, col + 1)];
+    if let Some(sq) = pawns.iter().flatten().copied().find(holding(pawn)) {
+        return Some(sq);
+    }
+
+    let knights = [
+        (-2, -1),
+        (-2, 1),
+        (-1, -2),
+        (-1, 2),
+        (1, -2),
+        (1, 2),
+        (2, -1),
+        (2, 1),
+    ];
+    if let Some(sq) = knights
+        .iter()
+        .filter_map(|&(dr, dc)| at(row + dr, col + dc))
+        .find(holding(knight))
+    {
+        return Some(sq);
+    }
+
+    let diagonal: Vec<i32> = [(-1, -1), (-1, 1), (1, -1), (1, 1)]
+        .iter()
+        .filter_map(|&(dr, dc)| first_piece_on_ray(squares, row, col, dr, dc))
+        .collect();
+    let straight: Vec<i32> = [(-1, 0), (1, 0), (0, -1), (0, 1)]
+        .iter()
+        .filter_map(|&(dr, dc)| first_piece_on_ray(squares, row, col, dr, dc))
+        .collect();
+    if let Some(sq) = diagonal.iter().copied().find(holding(bishop)) {
+        return Some(sq);
+    }
+    if let Some(sq) = straight.iter().copied().find(holding(rook)) {
+        return Some(sq);
+    }
+    if let Some(sq) = diagonal
+        .iter()
+        .chain(&straight)
+        .copied()
+        .find(holding(queen))
+    {
+        return Some(sq);
+    }
+
+    (-1..=1)
+        .flat_map(|dr| (-1..=1).map(move |dc| (dr, dc)))
+        .filter(|&d| d != (0, 0))
+        .filter_map(|(dr, dc)| at(row + dr, col + dc))
+        .find(holding(king))
+}
+
 fn check_diagonal_attack(board: &Board, square: i32, white: bool) -> bool {
     for &d in &BISHOP_DIRECTIONS {
         let mut current = square;
@@ -825,4 +924,61 @@ mod tests {
         let moves = generate_legal_moves(&board);
         assert_eq!(moves.len(), 147);
     }
+
+    #[test]
+    fn test_en_passant_removes_captured_pawn() {
+        let mut board = Board {
+            board: [Piece::Empty; 64],
+            white_to_move: true,
+            can_white_castle_kingside: false,
+            can_white_castle_queenside: false,
+            can_black_castle_kingside: false,
+            can_black_castle_queenside: false,
+            en_passant_square: to_index(2, 3), // d6
+            half_move_capture_or_pawn_clock: 0,
+            full_move_number: 1,
+        };
+        board.board[to_index(0, 4) as usize] = Piece::BK; // e8
+        board.board[to_index(3, 3) as usize] = Piece::BP; // d5
+        board.board[to_index(3, 4) as usize] = Piece::WP; // e5
+        board.board[to_index(7, 4) as usize] = Piece::WK; // e1
+
+        let ep = generate_legal_moves(&board)
+            .into_iter()
+            .find(|m| m.is_en_passant)
+            .unwrap();
+        make_move(&mut board, &ep);
+        assert_eq!(board.board[to_index(2, 3) as usize], Piece::WP);
+        assert_eq!(board.board[to_index(3, 3) as usize], Piece::Empty);
+        assert_eq!(board.board[to_index(3, 4) as usize], Piece::Empty);
+    }
+
+    #[test]
+    fn test_least_valuable_attacker_sees_through_removed_sliders() {
+        let mut squares = [Piece::Empty; 64];
+        let target = to_index(3, 3); // d5
+        squares[to_index(7, 3) as usize] = Piece::WQ; // d1
+        squares[to_index(5, 3) as usize] = Piece::WR; // d3
+        squares[to_index(4, 4) as usize] = Piece::WP; // e4
+        squares[to_index(2, 2) as usize] = Piece::BP; // c6
+
+        assert_eq!(
+            least_valuable_attacker(&squares, target, true),
+            Some(to_index(4, 4))
+        );
+        assert_eq!(
+            least_valuable_attacker(&squares, target, false),
+            Some(to_index(2, 2))
+        );
+        squares[to_index(4, 4) as usize] = Piece::Empty;
+        assert_eq!(
+            least_valuable_attacker(&squares, target, true),
+            Some(to_index(5, 3))
+        );
+        squares[to_index(5, 3) as usize] = Piece::Empty;
+        assert_eq!(
+            least_valuable_attacker(&squares, target, true),
+            Some(to_index(7, 3))
+        );
+    }
 }
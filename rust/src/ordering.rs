@@ -0,0 +1,327 @@
+use crate::board::{Board, Piece};
+use crate::chessmove::ChessMove;
+use crate::movegen::least_valuable_attacker;
+use crate::search::{MAX_PLY, PIECE_VALUES};
+use crate::tt::{pack_move, PackedMove};
+
+// History scores are kept within +-HISTORY_MAX by the gravity update.
+pub const HISTORY_MAX: i32 = 1 << 14;
+
+// Ordering tiers, far enough apart that each sorts as a block: hash move,
+// winning or even captures, killers, countermove, quiets by history and
+// finally losing captures.
+const TT_MOVE_SCORE: i32 = i32::MAX;
+const GOOD_NOISY_SCORE: i32 = 1 << 30;
+const KILLER_SCORE: i32 = 1 << 29;
+const COUNTERMOVE_SCORE: i32 = 1 << 28;
+const BAD_NOISY_SCORE: i32 = -(1 << 30);
+
+const PIECE_KINDS: usize = 13;
+
+/// A played move reduced to what the countermove and continuation tables
+/// are keyed on.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PieceTo {
+    pub piece: Piece,
+    pub to: i32,
+}
+
+pub fn is_quiet(mv: &ChessMove) -> bool {
+    mv.captured_piece == Piece::Empty && mv.promoted_piece == Piece::Empty && !mv.is_en_passant
+}
+
+fn centipawns(piece: Piece) -> i32 {
+    (PIECE_VALUES[piece as usize] * 100.0).round() as i32
+}
+
+fn is_white(piece: Piece) -> bool {
+    piece as usize <= Piece::WK as usize
+}
+
+/// Static exchange evaluation: the material in centipawns the mover nets
+/// if both sides keep recapturing on the target square with their
+/// cheapest piece for as long as it pays.
+pub fn see(board: &Board, mv: &ChessMove) -> i32 {
+    let mut squares = board.board;
+    let mover = squares[mv.from as usize];
+    let mut gain = if mv.is_en_passant {
+        let behind = if is_white(mover) { 8 } else { -8 };
+        squares[(mv.to + behind) as usize] = Piece::Empty;
+        centipawns(mv.captured_piece)
+    } else {
+        centipawns(squares[mv.to as usize])
+    };
+    let mut on_target = mover;
+    if mv.promoted_piece != Piece::Empty {
+        gain += centipawns(mv.promoted_piece) - centipawns(mover);
+        on_target = mv.promoted_piece;
+    }
+    squares[mv.from as usize] = Piece::Empty;
+    squares[mv.to as usize] = on_target;
+
+    // gains[i]: material for the side making capture i, if it is the last.
+    let mut gains = vec![gain];
+    let mut white = !is_white(mover);
+    while let Some(sq) = least_valuable_attacker(&squares, mv.to, white) {
+        gains.push(centipawns(on_target) - gains[gains.len() - 1]);
+        on_target = squares[sq as usize];
+        squares[sq as usize] = Piece::Empty;
+        squares[mv.to as usize] = on_target;
+        white = !white;
+    }
+    // Either side may decline to continue the exchange.
+    while gains.len() > 1 {
+        let last = gains.pop().unwrap();
+        let previous = gains.last_mut().unwrap();
+        *previous = -(-*previous).max(last);
+    }
+    gains[0]
+}
+
+// Most valuable victim first, then least valuable attacker.
+fn mvv_lva(board: &Board, mv: &ChessMove) -> i32 {
+    let attacker = (board.board[mv.from as usize] as i32 - 1) % 6;
+    centipawns(mv.captured_piece) * 8 + centipawns(mv.promoted_piece) - attacker
+}
+
+fn same_move(a: &ChessMove, b: &ChessMove) -> bool {
+    a.from == b.from && a.to == b.to && a.promoted_piece == b.promoted_piece
+}
+
+fn history_bonus(depth: i32) -> i32 {
+    (16 * depth * depth).min(HISTORY_MAX / 4)
+}
+
+// Moves the entry towards +-HISTORY_MAX by `bonus`, less so the closer it
+// already is, so old results fade instead of saturating.
+fn apply_gravity(entry: &mut i32, bonus: i32) {
+    *entry += bonus - *entry * bonus.abs() / HISTORY_MAX;
+}
+
+fn continuation_index(previous: PieceTo, piece: Piece, to: i32) -> usize {
+    ((previous.piece as usize * 64 + previous.to as usize) * PIECE_KINDS + piece as usize) * 64
+        + to as usize
+}
+
+/// Quiet move ordering tables learned during search. Each thread owns
+/// one; they persist across searches until cleared.
+pub struct MoveOrdering {
+    killers: [[Option<ChessMove>; 2]; MAX_PLY],
+    // Butterfly history by side to move, from and to square.
+    history: Box<[[[i32; 64]; 64]; 2]>,
+    // Last refutation of a move, by that move's piece and target square.
+    countermoves: Box<[[Option<ChessMove>; 64]; PIECE_KINDS]>,
+    // History of a move given the move one or two plies earlier.
+    continuation: Vec<i32>,
+}
+
+impl MoveOrdering {
+    pub fn new() -> Self {
+        MoveOrdering {
+            killers: [[None; 2]; MAX_PLY],
+            history: Box::new([[[0; 64]; 64]; 2]),
+            countermoves: Box::new([[None; 64]; PIECE_KINDS]),
+            continuation: vec![0; PIECE_KINDS * 64 * PIECE_KINDS * 64],
+        }
+    }
+
+    pub fn clear(&mut self) {
+        *self = MoveOrdering::new();
+    }
+
+    pub fn is_killer(&self, ply: usize, mv: &ChessMove) -> bool {
+        self.killers[ply]
+            .iter()
+            .flatten()
+            .any(|killer| same_move(killer, mv))
+    }
+
+    pub fn history(&self, white_to_move: bool, mv: &ChessMove) -> i32 {
+        self.history[white_to_move as usize][mv.from as usize][mv.to as usize]
+    }
+
+    /// Sorts `moves`, which must be legal: the hash move first, then
+    /// captures and promotions that do not lose material by MVV-LVA,
+    /// killers, the countermove, quiets by history and losing captures.
+    /// A hash move matching none of them, say after a key collision, is
+    /// ignored. `previous` holds the moves one and two plies back.
+    pub fn order(
+        &self,
+        board: &Board,
+        moves: &mut [ChessMove],
+        ply: usize,
+        tt_move: Option<PackedMove>,
+        previous: [Option<PieceTo>; 2],
+    ) {
+        let countermove =
+            previous[0].and_then(|p| self.countermoves[p.piece as usize][p.to as usize]);
+        moves.sort_by_cached_key(|mv| {
+            let score = if tt_move == Some(pack_move(mv)) {
+                TT_MOVE_SCORE
+            } else if !is_quiet(mv) {
+                let tier = if see(board, mv) >= 0 {
+                    GOOD_NOISY_SCORE
+                } else {
+                    BAD_NOISY_SCORE
+                };
+                tier + mvv_lva(board, mv)
+            } else if let Some(slot) = self.killers[ply]
+                .iter()
+                .position(|k| k.is_some_and(|k| same_move(&k, mv)))
+            {
+                KILLER_SCORE - slot as i32
+            } else if countermove.is_some_and(|c| same_move(&c, mv)) {
+                COUNTERMOVE_SCORE
+            } else {
+                self.quiet_score(board, mv, previous)
+            };
+            -score
+        });
+    }
+
+    fn quiet_score(&self, board: &Board, mv: &ChessMove, previous: [Option<PieceTo>; 2]) -> i32 {
+        let piece = board.board[mv.from as usize];
+        self.history(board.white_to_move, mv)
+            + previous
+                .iter()
+                .flatten()
+                .map(|&p| self.continuation[continuation_index(p, piece, mv.to)])
+                .sum::<i32>()
+    }
+
+    /// Rewards the quiet move that caused a cutoff and penalizes the quiet
+    /// moves searched before it. Moves are paired with the piece moved.
+    pub fn update_quiet(
+        &mut self,
+        white_to_move: bool,
+        ply: usize,
+        depth: i32,
+        previous: [Option<PieceTo>; 2],
+        best: (ChessMove, Piece),
+        tried: &[(ChessMove, Piece)],
+    ) {
+        let (best_move, _) = best;
+        let killers = &mut self.killers[ply];
+        if !killers[0].is_some_and(|k| same_move(&k, &best_move)) {
+            killers[1] = killers[0];
+            killers[0] = Some(best_move);
+        }
+        if let Some(p) = previous[0] {
+            self.countermoves[p.piece as usize][p.to as usize] = Some(best_move);
+        }
+
+        let bonus = history_bonus(depth);
+        let side = white_to_move as usize;
+        for (&(mv, piece), bonus) in std::iter::once(&best)
+            .map(|m| (m, bonus))
+            .chain(tried.iter().map(|m| (m, -bonus)))
+        {
+            apply_gravity(
+                &mut self.history[side][mv.from as usize][mv.to as usize],
+                bonus,
+            );
+            for p in previous.iter().flatten() {
+                apply_gravity(
+                    &mut self.continuation[continuation_index(*p, piece, mv.to)],
+                    bonus,
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::movegen::generate_legal_moves;
+    use crate::params::PositionParams;
+    use crate::position_utils::{parse_uci_move, set_board_position};
+
+    fn board_from_fen(fen: &str) -> Board {
+        let mut board = Board {
+            board: [Piece::Empty; 64],
+            white_to_move: true,
+            can_white_castle_kingside: false,
+            can_white_castle_queenside: false,
+            can_black_castle_kingside: false,
+            can_black_castle_queenside: false,
+            en_passant_square: -1,
+            half_move_capture_or_pawn_clock: 0,
+            full_move_number: 1,
+        };
+        let params = PositionParams {
+            is_fen: true,
+            position: fen.to_string(),
+            moves: vec![],
+        };
+        set_board_position(&mut board, &params).unwrap();
+        board
+    }
+
+    #[test]
+    fn test_gravity_keeps_history_bounded() {
+        let mut entry = 0;
+        for _ in 0..1000 {
+            apply_gravity(&mut entry, history_bonus(20));
+        }
+        assert!((1..=HISTORY_MAX).contains(&entry));
+        for _ in 0..1000 {
+            apply_gravity(&mut entry, -history_bonus(20));
+        }
+        assert!((-HISTORY_MAX..0).contains(&entry));
+    }
+
+    #[test]
+    fn test_cutoff_updates_killers_countermove_and_history() {
+        let mut ordering = MoveOrdering::new();
+        let previous = [
+            Some(PieceTo {
+                piece: Piece::BP,
+                to: 36,
+            }),
+            None,
+        ];
+        let best = ChessMove::quiet(62, 45);
+        let tried = ChessMove::quiet(57, 42);
+
+        ordering.update_quiet(
+            true,
+            3,
+            4,
+            previous,
+            (best, Piece::WN),
+            &[(tried, Piece::WN)],
+        );
+        assert!(ordering.is_killer(3, &best));
+        assert!(!ordering.is_killer(2, &best));
+        assert!(ordering.history(true, &best) > 0);
+        assert!(ordering.history(true, &tried) < 0);
+        assert_eq!(ordering.history(false, &best), 0);
+        assert_eq!(ordering.countermoves[Piece::BP as usize][36], Some(best));
+    }
+
+    #[test]
+    fn test_see_resolves_exchanges() {
+        let board = board_from_fen("4k3/8/3p4/4n3/3P4/8/8/4K3 w - - 0 1");
+        assert_eq!(see(&board, &parse_uci_move(&board, "d4e5").unwrap()), 220);
+
+        let board = board_from_fen("4k3/8/3p4/4p3/8/8/4Q3/4K3 w - - 0 1");
+        assert_eq!(see(&board, &parse_uci_move(&board, "e2e5").unwrap()), -800);
+
+        // The second rook recaptures through the first.
+        let board = board_from_fen("4k3/4r3/8/4p3/8/8/4R3/4R1K1 w - - 0 1");
+        assert_eq!(see(&board, &parse_uci_move(&board, "e2e5").unwrap()), 100);
+    }
+
+    #[test]
+    fn test_order_puts_hash_move_first_and_losing_captures_last() {
+        let board = board_from_fen("4k3/8/3p4/4p3/8/8/4Q3/4K3 w - - 0 1");
+        let quiet = parse_uci_move(&board, "e1d1").unwrap();
+        let losing = parse_uci_move(&board, "e2e5").unwrap();
+        let mut moves = generate_legal_moves(&board);
+
+        MoveOrdering::new().order(&board, &mut moves, 0, Some(pack_move(&quiet)), [None, None]);
+        assert_eq!(moves[0], quiet);
+        assert_eq!(*moves.last().unwrap(), losing);
+    }
+}
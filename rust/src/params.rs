@@ -2,7 +2,7 @@ use std::time::Duration;
 
 #[derive(Debug, Default)]
 pub struct GoParams {
-    pub searchmoves: Option<i32>,
+    pub searchmoves: Vec<String>,
     pub ponder: Option<bool>,
     pub wtime: Option<Duration>,
     pub btime: Option<Duration>,
@@ -10,7 +10,7 @@ pub struct GoParams {
     pub binc: Option<Duration>,
     pub movestogo: Option<i32>,
     pub depth: Option<i32>,
-    pub nodes: Option<i32>,
+    pub nodes: Option<u64>,
     pub mate: Option<i32>,
     pub movetime: Option<Duration>,
     pub infinite: bool,
@@ -29,9 +29,14 @@ pub struct SearchInfo<'a> {
     pub seldepth: Option<i32>,
     pub time: Option<Duration>,
     pub nodes: Option<u64>,
+    pub nps: Option<u64>,
+    pub hashfull: Option<i32>,
     pub multipv: Option<i32>,
     pub score_cp: Option<i32>,
     pub score_mate: Option<i32>,
+    // The score only bounds the true value after an aspiration fail.
+    pub lowerbound: bool,
+    pub upperbound: bool,
     pub pv: Option<&'a str>,
 }
 
@@ -40,3 +45,125 @@ pub struct OptionParams {
     pub name: String,
     pub value: String,
 }
+
+/// Search tuning knobs, each settable as a UCI spin option. Fractional
+/// values are in hundredths.
+#[derive(Clone, Debug)]
+pub struct SearchParams {
+    // Late move reductions: base + ln(depth) * ln(move number) / divisor plies.
+    pub lmr_base: i32,
+    pub lmr_divisor: i32,
+    pub lmr_min_depth: i32,
+    pub lmr_min_moves: i32,
+    // Late move pruning: up to the max depth, quiet moves after the first
+    // base + depth^2 are skipped.
+    pub lmp_max_depth: i32,
+    pub lmp_base: i32,
+    // Static evaluation pruning, margins in centipawns. Reverse futility
+    // cuts when eval - margin * depth still beats beta; futility skips
+    // quiet moves when eval + base + margin * depth cannot reach alpha;
+    // razoring drops to quiescence when eval + margin * depth is below it.
+    pub rfp_max_depth: i32,
+    pub rfp_margin: i32,
+    pub futility_max_depth: i32,
+    pub futility_base: i32,
+    pub futility_margin: i32,
+    pub razor_max_depth: i32,
+    pub razor_margin: i32,
+    // Internal iterative reduction: from this depth, a node without a hash
+    // move is searched one ply shallower.
+    pub iir_min_depth: i32,
+    // ProbCut: from this depth, a capture that beats beta plus the margin
+    // (centipawns) in a reduced search prunes a non-PV node.
+    pub probcut_min_depth: i32,
+    pub probcut_margin: i32,
+}
+
+impl Default for SearchParams {
+    fn default() -> Self {
+        SearchParams {
+            lmr_base: 75,
+            lmr_divisor: 225,
+            lmr_min_depth: 3,
+            lmr_min_moves: 3,
+            lmp_max_depth: 3,
+            lmp_base: 4,
+            rfp_max_depth: 6,
+            rfp_margin: 100,
+            futility_max_depth: 6,
+            futility_base: 100,
+            futility_margin: 100,
+            razor_max_depth: 1,
+            razor_margin: 300,
+            iir_min_depth: 4,
+            probcut_min_depth: 6,
+            probcut_margin: 200,
+        }
+    }
+}
+
+impl SearchParams {
+    /// Every knob as (option name, value, min, max).
+    fn fields(&mut self) -> Vec<(&'static str, &mut i32, i32, i32)> {
+        vec![
+            ("LMR Base", &mut self.lmr_base, 0, 300),
+            ("LMR Divisor", &mut self.lmr_divisor, 100, 1000),
+            ("LMR Min Depth", &mut self.lmr_min_depth, 2, 10),
+            ("LMR Min Moves", &mut self.lmr_min_moves, 1, 20),
+            ("LMP Max Depth", &mut self.lmp_max_depth, 0, 10),
+            ("LMP Base", &mut self.lmp_base, 1, 50),
+            ("RFP Max Depth", &mut self.rfp_max_depth, 0, 20),
+            ("RFP Margin", &mut self.rfp_margin, 0, 1000),
+            ("Futility Max Depth", &mut self.futility_max_depth, 0, 20),
+            ("Futility Base", &mut self.futility_base, 0, 1000),
+            ("Futility Margin", &mut self.futility_margin, 0, 1000),
+            ("Razor Max Depth", &mut self.razor_max_depth, 0, 10),
+            ("Razor Margin", &mut self.razor_margin, 0, 2000),
+            ("IIR Min Depth", &mut self.iir_min_depth, 1, 64),
+            ("ProbCut Min Depth", &mut self.probcut_min_depth, 2, 64),
+            ("ProbCut Margin", &mut self.probcut_margin, 0, 2000),
+        ]
+    }
+
+    /// The knobs as (option name, value, min, max), for `uci` output.
+    pub fn options(&self) -> Vec<(&'static str, i32, i32, i32)> {
+        self.clone()
+            .fields()
+            .into_iter()
+            .map(|(name, value, min, max)| (name, *value, min, max))
+            .collect()
+    }
+
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        let (option, field, min, max) = self
+            .fields()
+            .into_iter()
+            .find(|(option, ..)| option.eq_ignore_ascii_case(name))
+            .ok_or_else(|| format!("Unknown option: {}", name))?;
+        let parsed = value
+            .parse::<i32>()
+            .map_err(|e| format!("Invalid {} value '{}': {}", option, value, e))?;
+        *field = parsed.clamp(min, max);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_params_set_by_option_name() {
+        let mut params = SearchParams::default();
+        params.set("lmr divisor", "300").unwrap();
+        assert_eq!(params.lmr_divisor, 300);
+        params.set("LMP Base", "1000").unwrap();
+        assert_eq!(params.lmp_base, 50);
+        assert!(params.set("LMP Base", "many").is_err());
+        assert!(params.set("No Such Knob", "1").is_err());
+        assert!(params
+            .options()
+            .iter()
+            .any(|&(name, value, ..)| name == "LMR Divisor" && value == 300));
+    }
+}
@@ -1,4 +1,5 @@
-use crate::board::{Board, Piece};
+use crate::board::{Board, Piece, ZobristKey};
+use crate::chessmove::ChessMove;
 use crate::movegen::{generate_legal_moves, make_move};
 use crate::params::PositionParams;
 
@@ -142,63 +143,75 @@ fn algebraic_to_square(s: &str) -> Result<usize, String> {
     Ok(index)
 }
 
-pub fn set_board_position(board: &mut Board, params: &PositionParams) -> Result<(), String> {
-    if params.is_fen {
-        parse_fen(board, params.position.clone())?;
-    } else {
-        set_to_starting_position(board);
+pub fn parse_uci_move(board: &Board, mv_str: &str) -> Result<ChessMove, String> {
+    if mv_str.len() < 4 {
+        return Err(format!("Invalid move string '{}': too short", mv_str));
     }
-    for mv_str in &params.moves {
-        if mv_str.len() < 4 {
-            return Err(format!("Invalid move string '{}': too short", mv_str));
-        }
-        let from_sq = algebraic_to_square(&mv_str[0..2])? as i32;
-        let to_sq = algebraic_to_square(&mv_str[2..4])? as i32;
-        let mut promo_piece = Piece::Empty;
-        if mv_str.len() == 5 {
-            promo_piece = match mv_str.chars().nth(4).unwrap().to_ascii_lowercase() {
-                'q' => {
-                    if board.white_to_move {
-                        Piece::WQ
-                    } else {
-                        Piece::BQ
-                    }
+    let from_sq = algebraic_to_square(&mv_str[0..2])? as i32;
+    let to_sq = algebraic_to_square(&mv_str[2..4])? as i32;
+    let mut promo_piece = Piece::Empty;
+    if mv_str.len() == 5 {
+        promo_piece = match mv_str.chars().nth(4).unwrap().to_ascii_lowercase() {
+            'q' => {
+                if board.white_to_move {
+                    Piece::WQ
+                } else {
+                    Piece::BQ
                 }
-                'r' => {
-                    if board.white_to_move {
-                        Piece::WR
-                    } else {
-                        Piece::BR
-                    }
+            }
+            'r' => {
+                if board.white_to_move {
+                    Piece::WR
+                } else {
+                    Piece::BR
                 }
-                'n' => {
-                    if board.white_to_move {
-                        Piece::WN
-                    } else {
-                        Piece::BN
-                    }
+            }
+            'n' => {
+                if board.white_to_move {
+                    Piece::WN
+                } else {
+                    Piece::BN
                 }
-                'b' => {
-                    if board.white_to_move {
-                        Piece::WB
-                    } else {
-                        Piece::BB
-                    }
+            }
+            'b' => {
+                if board.white_to_move {
+                    Piece::WB
+                } else {
+                    Piece::BB
                 }
-                x => return Err(format!("Invalid promotion char '{}'", x)),
             }
-        }
-        let legal_moves = generate_legal_moves(board);
-        let found_move = legal_moves
-            .iter()
-            .find(|m| m.from == from_sq && m.to == to_sq && m.promoted_piece == promo_piece);
-        if let Some(chess_mv) = found_move {
-            make_move(board, chess_mv);
-        } else {
-            return Err(format!("Illegal move encountered: {}", mv_str));
+            x => return Err(format!("Invalid promotion char '{}'", x)),
         }
     }
-    Ok(())
+    let legal_moves = generate_legal_moves(board);
+    legal_moves
+        .into_iter()
+        .find(|m| m.from == from_sq && m.to == to_sq && m.promoted_piece == promo_piece)
+        .ok_or_else(|| format!("Illegal move encountered: {}", mv_str))
+}
+
+pub fn set_board_position(board: &mut Board, params: &PositionParams) -> Result<(), String> {
+    set_board_position_with_history(board, params).map(|_| ())
+}
+
+/// Like [`set_board_position`], also returning the keys of the positions
+/// the moves passed through, oldest first, for repetition detection.
+pub fn set_board_position_with_history(
+    board: &mut Board,
+    params: &PositionParams,
+) -> Result<Vec<ZobristKey>, String> {
+    if params.is_fen {
+        parse_fen(board, params.position.clone())?;
+    } else {
+        set_to_starting_position(board);
+    }
+    let mut history = Vec::with_capacity(params.moves.len());
+    for mv_str in &params.moves {
+        let chess_mv = parse_uci_move(board, mv_str)?;
+        history.push(board.compute_zobrist_key());
+        make_move(board, &chess_mv);
+    }
+    Ok(history)
 }
 
 #[cfg(test)]
@@ -308,4 +321,25 @@ mod tests {
         assert_eq!(board.half_move_capture_or_pawn_clock, 0);
         assert_eq!(board.full_move_number, 26);
     }
+
+    #[test]
+    fn test_parse_uci_move_rejects_illegal() {
+        let mut board = Board {
+            board: [Piece::Empty; 64],
+            white_to_move: true,
+            can_white_castle_kingside: true,
+            can_white_castle_queenside: true,
+            can_black_castle_kingside: true,
+            can_black_castle_queenside: true,
+            en_passant_square: -1,
+            half_move_capture_or_pawn_clock: 0,
+            full_move_number: 1,
+        };
+        set_to_starting_position(&mut board);
+        let mv = parse_uci_move(&board, "e2e4").unwrap();
+        assert_eq!(mv.from, 52);
+        assert_eq!(mv.to, 36);
+        assert!(parse_uci_move(&board, "e2e5").is_err());
+        assert!(parse_uci_move(&board, "e7e5").is_err());
+    }
 }
@@ -1,83 +1,212 @@
-use std::collections::HashMap;
-use std::sync::atomic::AtomicBool;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Barrier};
+use std::thread;
 use std::time::{Duration, Instant};
 
 use crate::board::{Board, Piece, ZobristKey};
 use crate::chessmove::{ChessMove, UndoInfo};
 use crate::movegen::{generate_legal_moves, is_king_in_check, make_move, row_of, to_index};
-
-const MAX_DEPTH: usize = 64;
-
-static PIECE_VALUES: [f64; 13] = [
+use crate::ordering::{is_quiet, see, MoveOrdering, PieceTo};
+use crate::params::{SearchInfo, SearchParams};
+use crate::skill::{Skill, SKILL_MULTI_PV};
+use crate::timeman::TimeManager;
+use crate::trace::{Decision, SearchTrace, Tracer};
+use crate::tt::{
+    pack_move, NodeType, TranspositionTable, TranspositionTableEntry, DEFAULT_HASH_MB,
+};
+
+pub const MAX_DEPTH: usize = 64;
+// Deepest ply a line may reach, extensions and quiescence included; sizes
+// every ply-indexed table.
+pub const MAX_PLY: usize = 128;
+// Time and the stop flag are polled once per this many nodes.
+const POLL_INTERVAL: u64 = 2048;
+pub const MATE_SCORE: f64 = 999_999.0;
+// Mates are scored MATE_SCORE - ply, so scores at least this large are
+// mates found within the search.
+pub const MATE_BOUND: f64 = MATE_SCORE - MAX_PLY as f64;
+// Added to every vote so the lowest scoring thread still counts.
+const VOTE_SCORE_OFFSET: f64 = 0.14;
+// Hash table values are rounded to centipawns.
+const TT_VALUE_RESOLUTION: f64 = 0.01;
+// Width of a zero window; well below the hash table's centipawn resolution.
+const NULL_WINDOW: f64 = 0.0001;
+// Aspiration windows start this far either side of the previous score and
+// double on every fail; beyond the limit the bound is dropped entirely.
+const ASPIRATION_MIN_DEPTH: i32 = 4;
+const ASPIRATION_DELTA: f64 = 0.25;
+const ASPIRATION_MAX_DELTA: f64 = 8.0;
+// Null-move pruning: the null move is searched this many plies shallower,
+// plus one ply per divisor of depth. From the verification depth up, a
+// null-move cutoff is only taken once a reduced normal search confirms it.
+const NULL_MOVE_MIN_DEPTH: i32 = 3;
+const NULL_MOVE_REDUCTION: i32 = 2;
+const NULL_MOVE_DEPTH_DIVISOR: i32 = 4;
+const NULL_MOVE_VERIFY_DEPTH: i32 = 8;
+// Singular extensions: from this depth, a hash move whose lower bound is at
+// most this many plies shallower is extended when every other move fails
+// low against its value less the margin per ply.
+const SINGULAR_MIN_DEPTH: i32 = 6;
+const SINGULAR_TT_DEPTH_SLACK: i32 = 3;
+const SINGULAR_MARGIN: f64 = 0.05;
+// ProbCut verifies its captures this many plies shallower.
+const PROBCUT_REDUCTION: i32 = 4;
+// A timed search stops early once the best move has survived this many
+// iterations with its score holding, some share of the budget is spent,
+// and a half-depth search shows every other move at least the margin worse.
+const EASY_MOVE_STABILITY: usize = 4;
+const EASY_MOVE_MAX_DROP: f64 = 0.1;
+const EASY_MOVE_TIME_FRACTION: f64 = 0.1;
+const EASY_MOVE_MARGIN: f64 = 1.5;
+
+pub static PIECE_VALUES: [f64; 13] = [
     0.0, 1.0, 3.2, 3.3, 5.0, 9.0, 1000.0, 1.0, 3.2, 3.3, 5.0, 9.0, 1000.0,
 ];
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
-pub enum NodeType {
-    PVNode,  // Exact
-    AllNode, // Alpha
-    CutNode, // Beta
+/// Per-`go` constraints on how far and over which root moves to search.
+#[derive(Clone, Debug, Default)]
+pub struct SearchLimits {
+    pub depth: i32,
+    pub move_time: Option<Duration>,
+    pub nodes: Option<u64>,
+    pub mate: Option<i32>,
+    pub time_manager: Option<TimeManager>,
+    /// Set while pondering; time limits apply once the engine clears it.
+    pub ponder: Option<Arc<AtomicBool>>,
+    pub search_moves: Vec<ChessMove>,
+    /// Keys of the game's positions before this one, oldest first.
+    pub history: Vec<ZobristKey>,
+    /// Centipawns the engine would give up to avoid a draw; negative
+    /// values seek draws. Zero for analysis.
+    pub contempt: i32,
+    /// Plays below full strength; see [`Skill`].
+    pub skill: Option<Skill>,
 }
 
-#[derive(Clone, Debug)]
-pub struct TranspositionTableEntry {
-    pub key: ZobristKey,
+impl SearchLimits {
+    pub fn with_depth(depth: i32) -> Self {
+        SearchLimits {
+            depth,
+            ..Default::default()
+        }
+    }
+}
+
+/// Nodes and time spent by the end of one iteration.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DepthStats {
     pub depth: i32,
-    pub value: f64,
-    pub node_type: NodeType,
-    pub best_move: ChessMove,
+    pub nodes: u64,
+    pub time: Duration,
 }
 
-#[derive(Clone)]
-pub struct TranspositionTable {
-    table: HashMap<ZobristKey, TranspositionTableEntry>,
+/// Counters from one `find_best_move`, summed over all threads, except
+/// `depths`, which follows the main thread's iterations.
+#[derive(Clone, Debug, Default)]
+pub struct SearchStats {
+    pub nodes: u64,
+    // Of `nodes`, those visited in quiescence search.
+    pub qnodes: u64,
+    // Hash probes in the main search that found an entry, and those whose
+    // bound settled the node.
+    pub tt_hits: u64,
+    pub tt_cutoffs: u64,
+    // Beta cutoffs in the main search, and how many came from the first move.
+    pub cutoffs: u64,
+    pub first_move_cutoffs: u64,
+    pub depths: Vec<DepthStats>,
+    // Why the search ended before its time ran out, if it did.
+    pub early_stop: Option<EarlyStop>,
+    // Iterations whose falling score extended the time budget.
+    pub score_drop_extensions: u32,
 }
 
-impl TranspositionTable {
-    pub fn new() -> Self {
-        TranspositionTable {
-            table: HashMap::new(),
-        }
+/// Reasons a timed search returns before its budget is spent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EarlyStop {
+    /// Only one legal move; it was searched one ply for a score.
+    SingleReply,
+    /// The best move was stable and clearly ahead of the alternatives.
+    EasyMove,
+}
+
+impl fmt::Display for EarlyStop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            EarlyStop::SingleReply => "single reply",
+            EarlyStop::EasyMove => "easy move",
+        })
     }
+}
 
-    pub fn probe(&self, key: ZobristKey) -> Option<TranspositionTableEntry> {
-        self.table.get(&key).cloned()
+impl SearchStats {
+    /// Share of beta cutoffs produced by the first move searched, a
+    /// measure of move ordering quality.
+    pub fn first_move_cutoff_rate(&self) -> Option<f64> {
+        (self.cutoffs > 0).then(|| self.first_move_cutoffs as f64 / self.cutoffs as f64)
     }
 
-    pub fn store(
-        &mut self,
-        key: ZobristKey,
-        value: f64,
-        node_type: NodeType,
-        depth: i32,
-        best_move: ChessMove,
-    ) {
-        let entry = TranspositionTableEntry {
-            key,
-            depth,
-            value,
-            node_type,
-            best_move,
+    /// Average factor by which each iteration outgrew the previous one.
+    pub fn effective_branching_factor(&self) -> Option<f64> {
+        let [.., previous, last] = self.depths.as_slice() else {
+            return None;
         };
-        self.table.insert(key, entry);
+        let first = self.depths[0].nodes.max(1) as f64;
+        let last_iteration = (last.nodes - previous.nodes).max(1) as f64;
+        Some((last_iteration / first).powf(1.0 / (self.depths.len() - 1) as f64))
     }
 
-    pub fn clear(&mut self) {
-        self.table.clear();
+    fn add_counters(&mut self, other: &SearchStats) {
+        self.qnodes += other.qnodes;
+        self.tt_hits += other.tt_hits;
+        self.tt_cutoffs += other.tt_cutoffs;
+        self.cutoffs += other.cutoffs;
+        self.first_move_cutoffs += other.first_move_cutoffs;
     }
 }
 
-#[derive(Clone)]
-struct KillerMoves {
-    moves: [Option<ChessMove>; 2],
+/// What a finished search settled on. `score` and `pv` are empty when
+/// there was no legal move to play.
+#[derive(Clone, Debug)]
+pub struct SearchResult {
+    pub best_move: Option<ChessMove>,
+    // The expected reply, for pondering.
+    pub ponder: Option<ChessMove>,
+    pub score: Option<f64>,
+    pub pv: Vec<ChessMove>,
+    // The deepest iteration completed.
+    pub depth: i32,
+    pub stats: SearchStats,
+    // The tree of the last completed iteration, when tracing.
+    pub trace: Option<SearchTrace>,
 }
 
-impl Default for KillerMoves {
-    fn default() -> Self {
-        KillerMoves {
-            moves: [None, None],
-        }
-    }
+/// Receives the output of a search, on the thread running it. UCI is one
+/// implementation; embedders can supply their own.
+pub trait SearchObserver: Send + Sync {
+    /// A line from a completed iteration, or a bound after an aspiration fail.
+    fn on_info(&self, info: &SearchInfo);
+    /// Diagnostics with no place in `SearchInfo`.
+    fn on_message(&self, message: &str);
+    /// The result, once the engine is ready to play it.
+    fn on_result(&self, result: &SearchResult);
+}
+
+/// Discards everything; the default until an observer is set.
+pub struct SilentObserver;
+
+impl SearchObserver for SilentObserver {
+    fn on_info(&self, _info: &SearchInfo) {}
+    fn on_message(&self, _message: &str) {}
+    fn on_result(&self, _result: &SearchResult) {}
+}
+
+/// One root move with its score and principal variation.
+#[derive(Clone, Debug)]
+pub struct PvLine {
+    pub score: f64,
+    pub moves: Vec<ChessMove>,
 }
 
 fn unmake_move(board: &mut Board, mv: &ChessMove, undo: &UndoInfo) {
@@ -133,76 +262,337 @@ fn unmake_move(board: &mut Board, mv: &ChessMove, undo: &UndoInfo) {
     }
 }
 
-fn is_killer_move(killers: &KillerMoves, mv: &ChessMove) -> bool {
-    for kopt in killers.moves.iter() {
-        if let Some(k) = kopt {
-            if k.from == mv.from && k.to == mv.to && k.promoted_piece == mv.promoted_piece {
-                return true;
-            }
-        }
+fn undo_info(board: &Board, mv: &ChessMove, key: ZobristKey) -> UndoInfo {
+    UndoInfo {
+        the_move: *mv,
+        piece_moved: board.board[mv.from as usize],
+        white_to_move_before: board.white_to_move,
+        can_white_castle_kingside_before: board.can_white_castle_kingside,
+        can_white_castle_queenside_before: board.can_white_castle_queenside,
+        can_black_castle_kingside_before: board.can_black_castle_kingside,
+        can_black_castle_queenside_before: board.can_black_castle_queenside,
+        en_passant_square_before: board.en_passant_square,
+        half_move_capture_or_pawn_clock_before: board.half_move_capture_or_pawn_clock,
+        full_move_number_before: board.full_move_number,
+        zobrist_key_before: key,
     }
-    false
 }
 
-fn store_killer_move(killers: &mut KillerMoves, mv: &ChessMove) {
-    if mv.captured_piece != Piece::Empty {
-        return;
-    }
-    if killers.moves.iter().any(|&kopt| {
-        kopt.map_or(false, |k| {
-            k.from == mv.from && k.to == mv.to && k.promoted_piece == mv.promoted_piece
-        })
-    }) {
-        return;
+/// Passes the move. Like a capture it resets the halfmove clock, so no
+/// repetition is found across it. Returns the en passant square and the
+/// clock to restore.
+fn make_null_move(board: &mut Board) -> (i32, i32) {
+    board.white_to_move = !board.white_to_move;
+    (
+        std::mem::replace(&mut board.en_passant_square, -1),
+        std::mem::replace(&mut board.half_move_capture_or_pawn_clock, 0),
+    )
+}
+
+fn unmake_null_move(board: &mut Board, (en_passant_square, clock): (i32, i32)) {
+    board.white_to_move = !board.white_to_move;
+    board.en_passant_square = en_passant_square;
+    board.half_move_capture_or_pawn_clock = clock;
+}
+
+/// Neither side can mate: bare kings, or a single knight or bishop.
+fn insufficient_material(board: &Board) -> bool {
+    let mut minors = 0;
+    for &p in &board.board {
+        match p {
+            Piece::Empty | Piece::WK | Piece::BK => {}
+            Piece::WN | Piece::WB | Piece::BN | Piece::BB => minors += 1,
+            _ => return false,
+        }
     }
-    killers.moves[1] = killers.moves[0];
-    killers.moves[0] = Some(*mv);
+    minors <= 1
+}
+
+/// Whether `white` has a piece other than pawns and the king. Without one,
+/// zugzwang is common and passing is no indication of strength.
+fn has_non_pawn_material(board: &Board, white: bool) -> bool {
+    let (first, last) = if white {
+        (Piece::WN, Piece::WQ)
+    } else {
+        (Piece::BN, Piece::BQ)
+    };
+    board
+        .board
+        .iter()
+        .any(|&p| (first as usize..=last as usize).contains(&(p as usize)))
+}
+
+fn centipawns(cp: i32) -> f64 {
+    cp as f64 / 100.0
+}
+
+fn gives_check(board: &mut Board, mv: &ChessMove) -> bool {
+    let undo = undo_info(board, mv, 0);
+    make_move(board, mv);
+    let check = is_king_in_check(board, board.white_to_move);
+    unmake_move(board, mv, &undo);
+    check
+}
+
+/// Checks first, then captures by victim value. With `checks_only` quiet
+/// non-checking moves are dropped, since only a check can mate next move.
+fn order_for_mate(board: &mut Board, moves: &[ChessMove], checks_only: bool) -> Vec<ChessMove> {
+    let mut ordered: Vec<(bool, ChessMove)> = moves
+        .iter()
+        .map(|mv| (gives_check(board, mv), *mv))
+        .filter(|(check, _)| *check || !checks_only)
+        .collect();
+    ordered.sort_by(|(check_a, a), (check_b, b)| {
+        check_b.cmp(check_a).then(
+            PIECE_VALUES[b.captured_piece as usize]
+                .total_cmp(&PIECE_VALUES[a.captured_piece as usize]),
+        )
+    });
+    ordered.into_iter().map(|(_, mv)| mv).collect()
 }
 
 pub struct Search {
-    tt: TranspositionTable,
-    killer_moves: [KillerMoves; MAX_DEPTH],
+    tt: Arc<TranspositionTable>,
+    // 0 for the main thread, which reports and owns the helpers.
+    thread_id: usize,
+    helpers: Vec<Search>,
+    // Node counts published by the other threads while they search: the
+    // helpers for the main thread, the main thread and the other helpers
+    // for a helper.
+    other_nodes: Vec<Arc<AtomicU64>>,
+    shared_nodes: Arc<AtomicU64>,
+    completed_depth: i32,
+    ordering: MoveOrdering,
+    // The move made at each ply of the current line; None for a null move.
+    played: [Option<PieceTo>; MAX_PLY],
+    // Move left out by a singular extension search at that ply.
+    excluded: [Option<ChessMove>; MAX_PLY],
+    root_depth: i32,
+    // Keys of the positions on the current line, by ply, and of the game
+    // before the root, for repetition detection.
+    path: [ZobristKey; MAX_PLY],
+    history: Vec<ZobristKey>,
+    // Draws score -contempt for the side to move at the root.
+    contempt: i32,
+    root_white: bool,
+    params: SearchParams,
+    observer: Arc<dyn SearchObserver>,
     search_start_time: Instant,
+    // Time limits count from here; differs from the start after a ponderhit.
+    clock_start: Instant,
+    ponder: Option<Arc<AtomicBool>>,
     move_time_limit: Option<Duration>,
+    // Time the search expects to use: the soft limit, or the whole movetime.
+    time_budget: Option<Duration>,
+    node_limit: Option<u64>,
+    nodes: u64,
+    // Deepest ply reached in the current iteration.
+    seldepth: usize,
+    stats: SearchStats,
+    stopped: bool,
+    stop_requested: Arc<AtomicBool>,
+    multi_pv: usize,
+    skill: Option<Skill>,
+    // Records the tree when debugging; only ever set on the main thread.
+    tracer: Option<Tracer>,
+    pv_lines: Vec<PvLine>,
 }
 
 impl Search {
     pub fn new() -> Self {
+        Search::with_table(0, Arc::new(TranspositionTable::new(DEFAULT_HASH_MB)))
+    }
+
+    fn with_table(thread_id: usize, tt: Arc<TranspositionTable>) -> Self {
         Search {
-            tt: TranspositionTable::new(),
-            killer_moves: core::array::from_fn(|_| KillerMoves::default()),
+            tt,
+            thread_id,
+            helpers: Vec::new(),
+            other_nodes: Vec::new(),
+            shared_nodes: Arc::new(AtomicU64::new(0)),
+            completed_depth: 0,
+            ordering: MoveOrdering::new(),
+            played: [None; MAX_PLY],
+            excluded: [None; MAX_PLY],
+            root_depth: 0,
+            path: [0; MAX_PLY],
+            history: Vec::new(),
+            contempt: 0,
+            root_white: true,
+            params: SearchParams::default(),
+            observer: Arc::new(SilentObserver),
             search_start_time: Instant::now(),
+            clock_start: Instant::now(),
+            ponder: None,
             move_time_limit: None,
+            time_budget: None,
+            node_limit: None,
+            nodes: 0,
+            seldepth: 0,
+            stats: SearchStats::default(),
+            stopped: false,
+            stop_requested: Arc::new(AtomicBool::new(false)),
+            multi_pv: 1,
+            skill: None,
+            tracer: None,
+            pv_lines: Vec::new(),
         }
     }
 
+    /// Material balance from the side to move's point of view.
     fn evaluate(&self, board: &Board) -> f64 {
-        let mut score = 0.0;
+        // Summed in whole centipawns: equal material must give bit-identical
+        // scores whatever order the pieces are visited in.
+        let mut centipawns: i64 = 0;
         for sq in 0..64 {
             let p = board.board[sq];
             if p != Piece::Empty {
-                score += PIECE_VALUES[p as usize]
-                    * if p as usize <= Piece::WK as usize {
-                        1.0
-                    } else {
-                        -1.0
-                    };
+                let value = (PIECE_VALUES[p as usize] * 100.0).round() as i64;
+                if p as usize <= Piece::WK as usize {
+                    centipawns += value;
+                } else {
+                    centipawns -= value;
+                }
             }
         }
+        if !board.white_to_move {
+            centipawns = -centipawns;
+        }
+        centipawns as f64 / 100.0
+    }
+
+    /// True while the engine ponders. The first call after a ponderhit
+    /// starts the clock, so pondering time is never charged to the move.
+    fn pondering(&mut self) -> bool {
+        let Some(flag) = &self.ponder else {
+            return false;
+        };
+        if flag.load(Ordering::Relaxed) {
+            return true;
+        }
+        self.ponder = None;
+        self.clock_start = Instant::now();
+        false
+    }
+
+    /// Like the clock, a stop request waits for a completed iteration. For
+    /// helpers this means each one searches, however soon the main thread
+    /// finishes.
+    fn asked_to_stop(&self) -> bool {
+        self.completed_depth > 0 && self.stop_requested.load(Ordering::Relaxed)
+    }
+
+    /// The clock only runs out once an iteration has completed, so there is
+    /// always a searched move to play.
+    fn time_up(&mut self) -> bool {
+        self.completed_depth > 0
+            && !self.pondering()
+            && self
+                .move_time_limit
+                .is_some_and(|limit| self.clock_start.elapsed() >= limit)
+    }
+
+    /// Returns true once any limit has been hit. The node limit counts all
+    /// threads; with one thread it is exact, so node-limited searches stop
+    /// at the same point every run.
+    fn check_stop(&mut self) -> bool {
+        if self.stopped {
+            return true;
+        }
+        let poll = self.nodes.is_multiple_of(POLL_INTERVAL);
+        // Under a node limit the other threads need an up-to-date count.
+        if poll || self.node_limit.is_some() {
+            self.shared_nodes.store(self.nodes, Ordering::Relaxed);
+        }
+        let out_of_nodes = self.node_limit.is_some_and(|limit| self.nodes() >= limit);
+        let interrupted = poll && (self.asked_to_stop() || self.time_up());
+        self.stopped = out_of_nodes || interrupted;
+        self.stopped
+    }
+
+    fn alpha_beta(
+        &mut self,
+        board: &mut Board,
+        depth: i32,
+        ply: usize,
+        alpha: f64,
+        beta: f64,
+        allow_null: bool,
+    ) -> f64 {
+        let Some(tracer) = self.tracer.as_mut() else {
+            return self.search_node(board, depth, ply, alpha, beta, allow_null);
+        };
+        tracer.enter(ply, depth, alpha, beta);
+        let score = self.search_node(board, depth, ply, alpha, beta, allow_null);
+        if let Some(tracer) = self.tracer.as_mut() {
+            tracer.exit(score);
+        }
         score
     }
 
-    fn alpha_beta(&mut self, board: &mut Board, depth: i32, mut alpha: f64, mut beta: f64) -> f64 {
-        let original_alpha = alpha;
-        if depth == 0 {
+    fn search_node(
+        &mut self,
+        board: &mut Board,
+        depth: i32,
+        ply: usize,
+        mut alpha: f64,
+        mut beta: f64,
+        allow_null: bool,
+    ) -> f64 {
+        if self.check_stop() {
+            return 0.0;
+        }
+        if depth <= 0 {
+            return self.quiescence(board, ply, alpha, beta, true);
+        }
+        self.nodes += 1;
+        self.seldepth = self.seldepth.max(ply);
+        if ply >= MAX_PLY - 1 {
             return self.evaluate(board);
         }
 
         let key = board.compute_zobrist_key();
-        if let Some(tt_entry) = self.tt.probe(key) {
+        self.path[ply] = key;
+        if self.is_repetition(key, ply, board.half_move_capture_or_pawn_clock)
+            || insufficient_material(board)
+        {
+            self.trace_decision(Decision::Draw);
+            return self.draw_score(board);
+        }
+        // Checkmate on the hundredth ply still counts.
+        if board.half_move_capture_or_pawn_clock >= 100
+            && !(is_king_in_check(board, board.white_to_move)
+                && generate_legal_moves(board).is_empty())
+        {
+            self.trace_decision(Decision::Draw);
+            return self.draw_score(board);
+        }
+
+        (alpha, beta) = mate_distance_window(alpha, beta, ply);
+        if alpha >= beta {
+            self.trace_decision(Decision::MateDistance);
+            return alpha;
+        }
+        let original_alpha = alpha;
+
+        // An exclusion search shares the key but not the result, so it
+        // neither reads nor writes the table.
+        let excluded = self.excluded[ply];
+        let tt_entry = match excluded {
+            None => self.tt.probe(key).map(|mut e| {
+                e.value = value_from_tt(e.value, ply);
+                e
+            }),
+            Some(_) => None,
+        };
+        let tt_move = tt_entry.as_ref().map(|e| e.best_move);
+        if let Some(tt_entry) = &tt_entry {
+            self.stats.tt_hits += 1;
             if tt_entry.depth >= depth {
                 match tt_entry.node_type {
                     NodeType::PVNode => {
+                        self.stats.tt_cutoffs += 1;
+                        self.trace_decision(Decision::TtCutoff);
                         return tt_entry.value;
                     }
                     NodeType::AllNode => {
@@ -217,52 +607,252 @@ impl Search {
                     }
                 }
                 if alpha >= beta {
+                    self.stats.tt_cutoffs += 1;
+                    self.trace_decision(Decision::TtCutoff);
                     return tt_entry.value;
                 }
             }
         }
 
-        let mut moves = generate_legal_moves(board);
+        let is_pv = beta - alpha > 2.0 * NULL_WINDOW;
+        let in_check = is_king_in_check(board, board.white_to_move);
+        let static_eval = self.evaluate(board);
+        // Static evaluation says nothing about mates, or while in check.
+        let prune =
+            !in_check && excluded.is_none() && alpha.abs() < MATE_BOUND && beta.abs() < MATE_BOUND;
+
+        // Reverse futility: far enough above beta, assume it holds.
+        if prune
+            && !is_pv
+            && depth <= self.params.rfp_max_depth
+            && static_eval - centipawns(self.params.rfp_margin * depth) >= beta
+        {
+            self.trace_decision(Decision::ReverseFutility);
+            return static_eval;
+        }
+
+        // Razoring: far below alpha, only captures could save the node.
+        if prune
+            && !is_pv
+            && depth <= self.params.razor_max_depth
+            && static_eval + centipawns(self.params.razor_margin * depth) < alpha
+        {
+            let value = self.quiescence(board, ply, alpha, alpha + NULL_WINDOW, true);
+            if self.stopped {
+                return 0.0;
+            }
+            if value <= alpha {
+                self.trace_decision(Decision::Razoring);
+                return value;
+            }
+        }
+
+        if allow_null
+            && excluded.is_none()
+            && !is_pv
+            && depth >= NULL_MOVE_MIN_DEPTH
+            && beta.abs() < MATE_BOUND
+            && has_non_pawn_material(board, board.white_to_move)
+            && !in_check
+            && static_eval >= beta
+        {
+            let reduced = depth - 1 - NULL_MOVE_REDUCTION - depth / NULL_MOVE_DEPTH_DIVISOR;
+            let null_undo = make_null_move(board);
+            self.played[ply] = None;
+            self.trace_move(ply, None);
+            let null_score = -self.alpha_beta(
+                board,
+                reduced.max(0),
+                ply + 1,
+                -beta,
+                -beta + NULL_WINDOW,
+                false,
+            );
+            unmake_null_move(board, null_undo);
+            if self.stopped {
+                return 0.0;
+            }
+
+            if null_score >= beta {
+                // A mate found after passing proves nothing.
+                let null_score = if null_score >= MATE_BOUND {
+                    beta
+                } else {
+                    null_score
+                };
+                if depth < NULL_MOVE_VERIFY_DEPTH {
+                    self.trace_decision(Decision::NullMove);
+                    return null_score;
+                }
+                let verified =
+                    self.alpha_beta(board, reduced.max(1), ply, beta - NULL_WINDOW, beta, false);
+                if self.stopped {
+                    return 0.0;
+                }
+                if verified >= beta {
+                    self.trace_decision(Decision::NullMove);
+                    return null_score;
+                }
+            }
+        }
 
-        let km = &self.killer_moves[depth as usize];
-        let mut insert_pos = 0_usize;
-        for i in 0..moves.len() {
-            if is_killer_move(km, &moves[i]) {
-                moves.swap(i, insert_pos);
-                insert_pos += 1;
+        if prune && !is_pv && depth >= self.params.probcut_min_depth {
+            if let Some(value) =
+                self.probcut(board, depth, ply, beta, static_eval, tt_entry.as_ref())
+            {
+                self.trace_decision(Decision::ProbCut);
+                return value;
+            }
+            if self.stopped {
+                return 0.0;
             }
         }
 
+        // Internal iterative reduction: without a hash move the ordering is
+        // a guess, so spend less on this visit; the next one will have a
+        // move to try first.
+        let depth = if tt_move.is_none() && excluded.is_none() && depth >= self.params.iir_min_depth
+        {
+            depth - 1
+        } else {
+            depth
+        };
+
+        let mut moves = generate_legal_moves(board);
+        let previous = self.previous_moves(ply);
+        self.ordering
+            .order(board, &mut moves, ply, tt_move, previous);
+
         if moves.is_empty() {
-            if is_king_in_check(board, board.white_to_move) {
-                return -999_999.0;
+            if in_check {
+                return -(MATE_SCORE - ply as f64);
+            }
+            return self.draw_score(board);
+        }
+
+        // Extensions stop once a path is twice the root depth, and well
+        // before MAX_PLY.
+        let can_extend = ply as i32 + depth < (2 * self.root_depth).min(MAX_DEPTH as i32 - 1);
+        let mut singular_move = None;
+        if let Some(entry) = &tt_entry {
+            if can_extend
+                && depth >= SINGULAR_MIN_DEPTH
+                && entry.node_type != NodeType::AllNode
+                && entry.depth >= depth - SINGULAR_TT_DEPTH_SLACK
+                && entry.value.abs() < MATE_BOUND
+            {
+                if let Some(&candidate) = moves.iter().find(|m| pack_move(m) == entry.best_move) {
+                    let singular_beta = entry.value - SINGULAR_MARGIN * depth as f64;
+                    self.excluded[ply] = Some(candidate);
+                    let value = self.alpha_beta(
+                        board,
+                        (depth - 1) / 2,
+                        ply,
+                        singular_beta - NULL_WINDOW,
+                        singular_beta,
+                        false,
+                    );
+                    self.excluded[ply] = None;
+                    if self.stopped {
+                        return 0.0;
+                    }
+                    if value < singular_beta {
+                        singular_move = Some(candidate);
+                    } else if singular_beta >= beta {
+                        // Multi-cut: the hash move and another move both
+                        // beat beta, so this node will fail high anyway.
+                        self.trace_decision(Decision::MultiCut);
+                        return singular_beta;
+                    }
+                }
             }
-            return 0.0;
         }
 
         let mut best_score = f64::NEG_INFINITY;
         let mut best_move = moves[0]; // fallback
         let node_type;
+        let white_to_move = board.white_to_move;
+        let mut quiets_tried: Vec<(ChessMove, Piece)> = Vec::new();
 
-        for mv in &moves {
-            let undo = UndoInfo {
-                the_move: *mv,
-                piece_moved: board.board[mv.from as usize],
-                white_to_move_before: board.white_to_move,
-                can_white_castle_kingside_before: board.can_white_castle_kingside,
-                can_white_castle_queenside_before: board.can_white_castle_queenside,
-                can_black_castle_kingside_before: board.can_black_castle_kingside,
-                can_black_castle_queenside_before: board.can_black_castle_queenside,
-                en_passant_square_before: board.en_passant_square,
-                half_move_capture_or_pawn_clock_before: board.half_move_capture_or_pawn_clock,
-                full_move_number_before: board.full_move_number,
-                zobrist_key_before: key,
-            };
-
+        for (i, mv) in moves.iter().enumerate() {
+            if excluded == Some(*mv) {
+                continue;
+            }
+            let quiet = is_quiet(mv);
+            let killer = quiet && self.ordering.is_killer(ply, mv);
+            let piece = board.board[mv.from as usize];
+            let undo = undo_info(board, mv, key);
+            self.played[ply] = Some(PieceTo { piece, to: mv.to });
+            self.trace_move(ply, Some(*mv));
             make_move(board, mv);
-            let score = -self.alpha_beta(board, depth - 1, -beta, -alpha);
+            let gives_check = is_king_in_check(board, board.white_to_move);
+
+            if quiet && !killer && !gives_check && best_score > -MATE_BOUND {
+                let late = !is_pv
+                    && !in_check
+                    && depth <= self.params.lmp_max_depth
+                    && i as i32 >= self.params.lmp_base + depth * depth;
+                let futile = prune
+                    && depth <= self.params.futility_max_depth
+                    && static_eval
+                        + centipawns(
+                            self.params.futility_base + self.params.futility_margin * depth,
+                        )
+                        <= alpha;
+                if late || futile {
+                    unmake_move(board, mv, &undo);
+                    if let Some(tracer) = self.tracer.as_mut() {
+                        let decision = if late {
+                            Decision::LateMovePruning
+                        } else {
+                            Decision::Futility
+                        };
+                        tracer.prune(*mv, depth - 1, alpha, beta, decision);
+                    }
+                    continue;
+                }
+            }
+
+            let extension = (can_extend && (gives_check || singular_move == Some(*mv))) as i32;
+            let new_depth = depth - 1 + extension;
+
+            let mut reduction = 0;
+            if quiet
+                && !in_check
+                && depth >= self.params.lmr_min_depth
+                && i as i32 >= self.params.lmr_min_moves
+            {
+                let good_history = self.ordering.history(white_to_move, mv) > 0;
+                reduction = self.late_move_reduction(depth, i + 1)
+                    - is_pv as i32
+                    - killer as i32
+                    - good_history as i32
+                    - gives_check as i32;
+                reduction = reduction.clamp(0, (new_depth - 1).max(0));
+            }
+            let score = if reduction > 0 {
+                let reduced = -self.alpha_beta(
+                    board,
+                    new_depth - reduction,
+                    ply + 1,
+                    -alpha - NULL_WINDOW,
+                    -alpha,
+                    true,
+                );
+                if reduced > alpha && !self.stopped {
+                    self.pvs_child(board, new_depth, ply + 1, alpha, beta, false)
+                } else {
+                    reduced
+                }
+            } else {
+                self.pvs_child(board, new_depth, ply + 1, alpha, beta, i == 0)
+            };
             unmake_move(board, mv, &undo);
 
+            if self.stopped {
+                return 0.0;
+            }
+
             if score > best_score {
                 best_score = score;
                 best_move = *mv;
@@ -272,9 +862,34 @@ impl Search {
             }
 
             if alpha >= beta {
-                store_killer_move(&mut self.killer_moves[depth as usize], mv);
+                self.stats.cutoffs += 1;
+                if i == 0 {
+                    self.stats.first_move_cutoffs += 1;
+                }
+                if quiet {
+                    self.ordering.update_quiet(
+                        white_to_move,
+                        ply,
+                        depth,
+                        previous,
+                        (*mv, piece),
+                        &quiets_tried,
+                    );
+                }
                 break;
             }
+            if quiet {
+                quiets_tried.push((*mv, piece));
+            }
+        }
+
+        if excluded.is_some() {
+            // Nothing but the excluded move: it is singular.
+            return if best_score == f64::NEG_INFINITY {
+                alpha
+            } else {
+                best_score
+            };
         }
 
         if best_score <= original_alpha {
@@ -285,149 +900,1527 @@ impl Search {
             node_type = NodeType::PVNode;
         }
 
-        self.tt.store(key, best_score, node_type, depth, best_move);
+        self.tt.store(
+            key,
+            value_to_tt(best_score, ply),
+            node_type,
+            depth,
+            &best_move,
+        );
 
         best_score
     }
 
-    pub fn find_best_move(
+    /// Resolves captures until the position is quiet, so the evaluation is
+    /// not taken in the middle of an exchange. In check every evasion is
+    /// searched; otherwise the side to move may stand pat on the static
+    /// evaluation and only captures and promotions that do not lose
+    /// material are tried, plus quiet checks with `checks`, which callers
+    /// set at the first level so quiet mates are not missed.
+    fn quiescence(
         &mut self,
         board: &mut Board,
-        depth: i32,
-        stop_requested: &AtomicBool,
-        move_time: Option<Duration>,
-    ) -> Option<ChessMove> {
-        self.search_start_time = Instant::now();
-        self.move_time_limit = move_time;
+        ply: usize,
+        mut alpha: f64,
+        beta: f64,
+        checks: bool,
+    ) -> f64 {
+        if self.check_stop() {
+            return 0.0;
+        }
+        self.nodes += 1;
+        self.stats.qnodes += 1;
+        self.seldepth = self.seldepth.max(ply);
+        if ply >= MAX_PLY - 1 {
+            return self.evaluate(board);
+        }
+        // Captures reset the halfmove clock, so repetitions are left to
+        // the main search.
+        if insufficient_material(board) {
+            return self.draw_score(board);
+        }
 
-        let moves = generate_legal_moves(board);
-        if moves.is_empty() {
-            return None;
+        let in_check = is_king_in_check(board, board.white_to_move);
+        let mut best_score = if in_check {
+            f64::NEG_INFINITY
+        } else {
+            self.evaluate(board)
+        };
+        if best_score >= beta {
+            return best_score;
         }
-        let mut best_score = f64::NEG_INFINITY;
-        let mut best_move = moves[0];
+        alpha = alpha.max(best_score);
 
-        let mut alpha = f64::NEG_INFINITY;
-        let beta = f64::INFINITY;
+        let mut moves = generate_legal_moves(board);
+        if moves.is_empty() {
+            return if in_check {
+                -(MATE_SCORE - ply as f64)
+            } else {
+                self.draw_score(board)
+            };
+        }
+        if !in_check {
+            moves.retain(|mv| {
+                if is_quiet(mv) {
+                    checks && gives_check(board, mv)
+                } else {
+                    see(board, mv) >= 0
+                }
+            });
+        }
+        self.ordering
+            .order(board, &mut moves, ply, None, [None, None]);
 
-        for mv in moves {
-            if stop_requested.load(std::sync::atomic::Ordering::Relaxed) {
-                return Some(best_move);
+        let key = board.compute_zobrist_key();
+        for mv in &moves {
+            let undo = undo_info(board, mv, key);
+            make_move(board, mv);
+            let score = -self.quiescence(board, ply + 1, -beta, -alpha, false);
+            unmake_move(board, mv, &undo);
+            if self.stopped {
+                return 0.0;
             }
-            if let Some(limit) = self.move_time_limit {
-                let elapsed = Instant::now().duration_since(self.search_start_time);
-                if elapsed >= limit {
-                    return Some(best_move);
+
+            if score > best_score {
+                best_score = score;
+                if score > alpha {
+                    alpha = score;
                 }
             }
+            if alpha >= beta {
+                break;
+            }
+        }
+        best_score
+    }
 
-            let key = board.compute_zobrist_key();
-            let undo = UndoInfo {
-                the_move: mv,
-                piece_moved: board.board[mv.from as usize],
-                white_to_move_before: board.white_to_move,
-                can_white_castle_kingside_before: board.can_white_castle_kingside,
-                can_white_castle_queenside_before: board.can_white_castle_queenside,
-                can_black_castle_kingside_before: board.can_black_castle_kingside,
-                can_black_castle_queenside_before: board.can_black_castle_queenside,
-                en_passant_square_before: board.en_passant_square,
-                half_move_capture_or_pawn_clock_before: board.half_move_capture_or_pawn_clock,
-                full_move_number_before: board.full_move_number,
-                zobrist_key_before: key,
-            };
-
-            make_move(board, &mv);
-            let score = -self.alpha_beta(board, depth - 1, -beta, -alpha);
-            unmake_move(board, &mv, &undo);
+    /// ProbCut: if a capture that wins material beats `beta` by a margin
+    /// in a shallow search, the full-depth search would very likely fail
+    /// high too. Returns the value to cut with, after storing it. A hash
+    /// entry as deep as the verification already showing the raised beta
+    /// out of reach skips the attempt.
+    fn probcut(
+        &mut self,
+        board: &mut Board,
+        depth: i32,
+        ply: usize,
+        beta: f64,
+        static_eval: f64,
+        tt_entry: Option<&TranspositionTableEntry>,
+    ) -> Option<f64> {
+        let probcut_beta = beta + centipawns(self.params.probcut_margin);
+        let reduced = depth - PROBCUT_REDUCTION;
+        if tt_entry.is_some_and(|e| e.depth >= reduced && e.value < probcut_beta) {
+            return None;
+        }
+        // Captures must win at least the gap to the raised beta outright.
+        let threshold = (((probcut_beta - static_eval) * 100.0).ceil() as i32).max(0);
+        let mut moves = generate_legal_moves(board);
+        moves.retain(|mv| !is_quiet(mv) && see(board, mv) >= threshold);
+        let key = board.compute_zobrist_key();
+        let previous = self.previous_moves(ply);
+        self.ordering.order(
+            board,
+            &mut moves,
+            ply,
+            tt_entry.map(|e| e.best_move),
+            previous,
+        );
 
-            if score > best_score {
-                best_score = score;
-                best_move = mv;
+        for mv in &moves {
+            let undo = undo_info(board, mv, key);
+            self.played[ply] = Some(PieceTo {
+                piece: board.board[mv.from as usize],
+                to: mv.to,
+            });
+            self.trace_move(ply, Some(*mv));
+            make_move(board, mv);
+            // Quiescence first: most captures fail there already.
+            let mut value = -self.quiescence(
+                board,
+                ply + 1,
+                -probcut_beta,
+                -probcut_beta + NULL_WINDOW,
+                false,
+            );
+            if value >= probcut_beta && reduced > 0 {
+                value = -self.alpha_beta(
+                    board,
+                    reduced,
+                    ply + 1,
+                    -probcut_beta,
+                    -probcut_beta + NULL_WINDOW,
+                    true,
+                );
             }
-            if score > alpha {
-                alpha = score;
+            unmake_move(board, mv, &undo);
+            if self.stopped {
+                return None;
+            }
+            if value >= probcut_beta {
+                self.tt.store(
+                    key,
+                    value_to_tt(value, ply),
+                    NodeType::CutNode,
+                    reduced + 1,
+                    mv,
+                );
+                return Some(value);
             }
         }
+        None
+    }
 
-        Some(best_move)
+    /// Whether the position at `ply` occurred before on the current line
+    /// or in the game. Only positions since the last capture or pawn move
+    /// with the same side to move can match.
+    fn is_repetition(&self, key: ZobristKey, ply: usize, halfmove_clock: i32) -> bool {
+        self.path[..ply]
+            .iter()
+            .rev()
+            .chain(self.history.iter().rev())
+            .take(halfmove_clock.max(0) as usize)
+            .skip(1)
+            .step_by(2)
+            .any(|&k| k == key)
     }
-}
 
-impl Clone for Search {
-    fn clone(&self) -> Self {
-        Search {
-            tt: self.tt.clone(),
-            killer_moves: self.killer_moves.clone(),
-            search_start_time: std::time::Instant::now(), // reset
-            move_time_limit: self.move_time_limit,
+    /// A draw from the side to move's point of view: contempt makes it a
+    /// loss for the engine and a gain for its opponent.
+    fn draw_score(&self, board: &Board) -> f64 {
+        let contempt = centipawns(self.contempt);
+        if board.white_to_move == self.root_white {
+            -contempt
+        } else {
+            contempt
         }
     }
-}
 
-pub fn move_to_uci(mv: &ChessMove) -> String {
-    use crate::movegen::{col_of, row_of};
-    let from_file = (col_of(mv.from) as u8 + b'a') as char;
-    let from_rank = (7 - row_of(mv.from)) as u8 + b'1';
-    let to_file = (col_of(mv.to) as u8 + b'a') as char;
-    let to_rank = (7 - row_of(mv.to)) as u8 + b'1';
-    let mut s = format!(
-        "{}{}{}{}",
-        from_file as char, from_rank as char, to_file as char, to_rank as char
-    );
-    if mv.promoted_piece != Piece::Empty {
-        let c = match mv.promoted_piece {
-            Piece::WQ | Piece::BQ => 'q',
-            Piece::WR | Piece::BR => 'r',
-            Piece::WN | Piece::BN => 'n',
-            Piece::WB | Piece::BB => 'b',
-            _ => '?',
-        };
-        s.push(c);
+    /// Plies to take off a quiet move searched `move_number`th at `depth`.
+    fn late_move_reduction(&self, depth: i32, move_number: usize) -> i32 {
+        let base = self.params.lmr_base as f64 / 100.0;
+        let divisor = self.params.lmr_divisor as f64 / 100.0;
+        (base + (depth as f64).ln() * (move_number as f64).ln() / divisor) as i32
     }
-    s
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::sync::atomic::AtomicBool;
-
-    use crate::params::PositionParams;
-    use crate::position_utils::set_board_position;
+    /// Searches a child with the full window if it is the first move, and
+    /// otherwise with a zero window that is only widened when the move
+    /// turns out to beat alpha.
+    fn pvs_child(
+        &mut self,
+        board: &mut Board,
+        depth: i32,
+        ply: usize,
+        alpha: f64,
+        beta: f64,
+        first: bool,
+    ) -> f64 {
+        if !first && alpha.is_finite() {
+            let score = -self.alpha_beta(board, depth, ply, -alpha - NULL_WINDOW, -alpha, true);
+            if score <= alpha || score >= beta || self.stopped {
+                return score;
+            }
+        }
+        -self.alpha_beta(board, depth, ply, -beta, -alpha, true)
+    }
 
-    #[test]
-    fn find_best_move_white_mate_in_one() {
-        let mut board = Board {
-            board: [Piece::Empty; 64],
-            white_to_move: true,
-            can_white_castle_kingside: false,
-            can_white_castle_queenside: false,
-            can_black_castle_kingside: false,
-            can_black_castle_queenside: false,
-            en_passant_square: -1,
-            half_move_capture_or_pawn_clock: 0,
-            full_move_number: 1,
-        };
-        let params = PositionParams {
-            is_fen: true,
-            position: "4k3/8/4K3/8/8/8/8/7R w - - 0 1".to_string(),
-            moves: vec![],
-        };
-        set_board_position(&mut board, &params).unwrap();
+    fn trace_move(&mut self, ply: usize, mv: Option<ChessMove>) {
+        if let Some(tracer) = self.tracer.as_mut() {
+            tracer.set_move(ply, mv);
+        }
+    }
 
+    fn trace_decision(&mut self, decision: Decision) {
+        if let Some(tracer) = self.tracer.as_mut() {
+            tracer.decide(decision);
+        }
+    }
+
+    /// The moves one and two plies before `ply`, for move ordering.
+    fn previous_moves(&self, ply: usize) -> [Option<PieceTo>; 2] {
+        let back = |n: usize| ply.checked_sub(n).and_then(|p| self.played[p]);
+        [back(1), back(2)]
+    }
+
+    /// Proves mates only: no evaluation, so a node without a forced mate
+    /// within `depth` plies scores 0. Mates are scored `MATE_SCORE - ply`.
+    fn mate_search(
+        &mut self,
+        board: &mut Board,
+        depth: i32,
+        ply: i32,
+        mut alpha: f64,
+        mut beta: f64,
+        pv: &mut Vec<ChessMove>,
+    ) -> f64 {
+        pv.clear();
+        if self.check_stop() {
+            return 0.0;
+        }
+        self.nodes += 1;
+        self.seldepth = self.seldepth.max(ply as usize);
+
+        (alpha, beta) = mate_distance_window(alpha, beta, ply as usize);
+        if alpha >= beta {
+            return alpha;
+        }
+
+        let moves = generate_legal_moves(board);
+        if moves.is_empty() {
+            if is_king_in_check(board, board.white_to_move) {
+                return -(MATE_SCORE - ply as f64);
+            }
+            return 0.0;
+        }
+        if depth == 0 {
+            return 0.0;
+        }
+
+        let attacker = ply % 2 == 0;
+        let ordered = order_for_mate(board, &moves, attacker && depth == 1);
+
+        let key = board.compute_zobrist_key();
+        let mut child_pv = Vec::new();
+        for mv in &ordered {
+            let undo = undo_info(board, mv, key);
+            make_move(board, mv);
+            let score = -self.mate_search(board, depth - 1, ply + 1, -beta, -alpha, &mut child_pv);
+            unmake_move(board, mv, &undo);
+
+            if self.stopped {
+                return 0.0;
+            }
+            if score > alpha {
+                alpha = score;
+                pv.clear();
+                pv.push(*mv);
+                pv.extend_from_slice(&child_pv);
+                if alpha >= beta {
+                    break;
+                }
+            }
+        }
+
+        alpha
+    }
+
+    /// Deepens one move at a time up to `max_moves`, so the first mate found
+    /// is also the shortest.
+    fn find_mate(
+        &mut self,
+        board: &mut Board,
+        root_moves: &[ChessMove],
+        max_moves: i32,
+    ) -> Option<PvLine> {
+        let key = board.compute_zobrist_key();
+        for moves in 1..=max_moves.max(1) {
+            let depth = 2 * moves - 1;
+            self.seldepth = 0;
+            let mut child_pv = Vec::new();
+            for mv in order_for_mate(board, root_moves, depth == 1) {
+                let undo = undo_info(board, &mv, key);
+                make_move(board, &mv);
+                let score =
+                    -self.mate_search(board, depth - 1, 1, -MATE_SCORE, MATE_SCORE, &mut child_pv);
+                unmake_move(board, &mv, &undo);
+
+                if self.stopped {
+                    return None;
+                }
+                if score >= MATE_SCORE - depth as f64 {
+                    let mut pv = vec![mv];
+                    pv.extend_from_slice(&child_pv);
+                    let line = PvLine { score, moves: pv };
+                    self.completed_depth = depth;
+                    self.report_line(depth, 0, &line, NodeType::PVNode);
+                    return Some(line);
+                }
+            }
+            self.completed_depth = depth;
+            if self.thread_id == 0 {
+                self.observer.on_info(&self.progress_info(depth));
+            }
+        }
+        None
+    }
+
+    fn search_root(
+        &mut self,
+        board: &mut Board,
+        depth: i32,
+        root_moves: &[ChessMove],
+        excluded: &[ChessMove],
+        mut alpha: f64,
+        beta: f64,
+    ) -> Option<(ChessMove, f64)> {
+        let mut best: Option<(ChessMove, f64)> = None;
+        self.root_depth = depth;
+        self.root_white = board.white_to_move;
+        self.path[0] = board.compute_zobrist_key();
+        if let Some(tracer) = self.tracer.as_mut() {
+            tracer.enter(0, depth, alpha, beta);
+        }
+
+        for mv in root_moves {
+            if excluded.contains(mv) {
+                continue;
+            }
+            if self.asked_to_stop() || self.time_up() {
+                self.stopped = true;
+            }
+            if self.stopped {
+                return None;
+            }
+
+            let key = board.compute_zobrist_key();
+            let undo = undo_info(board, mv, key);
+
+            self.played[0] = Some(PieceTo {
+                piece: board.board[mv.from as usize],
+                to: mv.to,
+            });
+            self.trace_move(0, Some(*mv));
+            make_move(board, mv);
+            let score = self.pvs_child(board, depth - 1, 1, alpha, beta, best.is_none());
+            unmake_move(board, mv, &undo);
+
+            if self.stopped {
+                return None;
+            }
+
+            if best.is_none_or(|(_, best_score)| score > best_score) {
+                best = Some((*mv, score));
+            }
+            if score > alpha {
+                alpha = score;
+            }
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        if let (Some(tracer), Some((_, score))) = (self.tracer.as_mut(), best) {
+            tracer.exit(score);
+        }
+        best
+    }
+
+    /// Follows hash moves from `first` to rebuild the principal variation.
+    /// Stops at all-nodes, whose stored move is only a fallback, unless
+    /// the bound equals the line's `score`: zero-window searches store
+    /// exact PV values that way when moves tie.
+    fn extract_pv(
+        &self,
+        board: &Board,
+        first: ChessMove,
+        score: f64,
+        max_len: usize,
+    ) -> Vec<ChessMove> {
+        let mut pv = vec![first];
+        let mut pv_board = board.clone();
+        make_move(&mut pv_board, &first);
+        let mut seen = vec![pv_board.compute_zobrist_key()];
+        let mut expected = -score;
+
+        while pv.len() < max_len {
+            let key = pv_board.compute_zobrist_key();
+            let entry = match self.tt.probe(key) {
+                Some(e)
+                    if e.node_type != NodeType::AllNode
+                        || (value_from_tt(e.value, pv.len()) - expected).abs()
+                            < TT_VALUE_RESOLUTION / 2.0 =>
+                {
+                    e
+                }
+                _ => break,
+            };
+            expected = -expected;
+            let Some(mv) = generate_legal_moves(&pv_board)
+                .into_iter()
+                .find(|m| pack_move(m) == entry.best_move)
+            else {
+                break;
+            };
+            make_move(&mut pv_board, &mv);
+            pv.push(mv);
+
+            let next_key = pv_board.compute_zobrist_key();
+            if seen.contains(&next_key) {
+                break;
+            }
+            seen.push(next_key);
+        }
+        pv
+    }
+
+    /// `bound` is the node type the score was proven as: exact for a
+    /// completed line, cut or all after an aspiration fail.
+    /// Only the main thread reports, and only the lines the GUI asked for;
+    /// a weakened search looks at more.
+    fn reports_line(&self, pv_index: usize) -> bool {
+        self.thread_id == 0 && pv_index < self.multi_pv
+    }
+
+    fn report_line(&self, depth: i32, pv_index: usize, line: &PvLine, bound: NodeType) {
+        let pv_str = line
+            .moves
+            .iter()
+            .map(move_to_uci)
+            .collect::<Vec<_>>()
+            .join(" ");
+        let (score_cp, score_mate) = if line.score.abs() >= MATE_BOUND {
+            // The score counts plies to mate; the PV may stop short of it.
+            let plies = (MATE_SCORE - line.score.abs()).round() as i32;
+            (
+                None,
+                Some(if line.score > 0.0 {
+                    (plies + 1) / 2
+                } else {
+                    -plies / 2
+                }),
+            )
+        } else {
+            (Some((line.score * 100.0).round() as i32), None)
+        };
+        self.observer.on_info(&SearchInfo {
+            multipv: Some(pv_index as i32 + 1),
+            score_cp,
+            score_mate,
+            lowerbound: bound == NodeType::CutNode,
+            upperbound: bound == NodeType::AllNode,
+            pv: Some(&pv_str),
+            ..self.progress_info(depth)
+        });
+    }
+
+    /// Depth, nodes and time so far, without a line. Sent alone, it shows
+    /// that a search with no line to report yet is still deepening.
+    fn progress_info<'a>(&self, depth: i32) -> SearchInfo<'a> {
+        let elapsed = self.search_start_time.elapsed();
+        let nodes = self.nodes();
+        let nps = (nodes as f64 / elapsed.as_secs_f64().max(0.001)) as u64;
+        SearchInfo {
+            depth: Some(depth),
+            seldepth: Some(self.seldepth as i32),
+            time: Some(elapsed),
+            nodes: Some(nodes),
+            nps: Some(nps),
+            hashfull: Some(self.tt.hashfull()),
+            multipv: None,
+            score_cp: None,
+            score_mate: None,
+            lowerbound: false,
+            upperbound: false,
+            pv: None,
+        }
+    }
+
+    pub fn set_hash_size(&mut self, size_mb: usize) {
+        self.tt = Arc::new(TranspositionTable::new(size_mb));
+        for helper in &mut self.helpers {
+            helper.tt = Arc::clone(&self.tt);
+        }
+    }
+
+    /// Total search threads, including this one. Helpers share the hash
+    /// table but keep their own move ordering tables.
+    pub fn set_threads(&mut self, threads: usize) {
+        self.helpers = (1..threads.max(1))
+            .map(|id| {
+                let mut helper = Search::with_table(id, Arc::clone(&self.tt));
+                helper.params = self.params.clone();
+                helper
+            })
+            .collect();
+        let counters: Vec<Arc<AtomicU64>> = std::iter::once(&self.shared_nodes)
+            .chain(self.helpers.iter().map(|h| &h.shared_nodes))
+            .cloned()
+            .collect();
+        self.other_nodes = counters[1..].to_vec();
+        for (i, helper) in self.helpers.iter_mut().enumerate() {
+            helper.other_nodes = counters
+                .iter()
+                .enumerate()
+                .filter(|&(j, _)| j != i + 1)
+                .map(|(_, counter)| Arc::clone(counter))
+                .collect();
+        }
+    }
+
+    /// Forgets everything learned in earlier searches: hash entries and
+    /// move ordering tables.
+    pub fn clear(&mut self) {
+        self.tt.clear();
+        self.clear_move_ordering();
+        for helper in &mut self.helpers {
+            helper.clear_move_ordering();
+        }
+    }
+
+    fn clear_move_ordering(&mut self) {
+        self.ordering.clear();
+    }
+
+    /// Where reports from the main thread go; helpers never report.
+    pub fn set_observer(&mut self, observer: Arc<dyn SearchObserver>) {
+        self.observer = observer;
+    }
+
+    /// Sets one of the tuning knobs in [`SearchParams`] by option name.
+    pub fn set_param(&mut self, name: &str, value: &str) -> Result<(), String> {
+        self.params.set(name, value)?;
+        for helper in &mut self.helpers {
+            helper.params = self.params.clone();
+        }
+        Ok(())
+    }
+
+    /// Records the tree of each iteration down to `max_ply`, or stops
+    /// recording. Costs speed; meant for debugging.
+    pub fn set_trace_depth(&mut self, max_ply: Option<usize>) {
+        self.tracer = max_ply.map(|max_ply| Tracer::new(max_ply.min(MAX_PLY - 1)));
+    }
+
+    pub fn set_multi_pv(&mut self, multi_pv: usize) {
+        self.multi_pv = multi_pv.max(1);
+    }
+
+    pub fn pv_lines(&self) -> &[PvLine] {
+        &self.pv_lines
+    }
+
+    /// The expected reply from the main line, if the search got that far.
+    pub fn ponder_move(&self) -> Option<ChessMove> {
+        self.pv_lines
+            .first()
+            .and_then(|line| line.moves.get(1))
+            .copied()
+    }
+
+    /// Nodes searched so far by all threads.
+    pub fn nodes(&self) -> u64 {
+        self.nodes
+            + self
+                .other_nodes
+                .iter()
+                .map(|n| n.load(Ordering::Relaxed))
+                .sum::<u64>()
+    }
+
+    fn prepare(&mut self, limits: &SearchLimits, stop_requested: &Arc<AtomicBool>) {
+        self.search_start_time = Instant::now();
+        self.clock_start = self.search_start_time;
+        self.ponder = limits.ponder.clone();
+        self.history = limits.history.clone();
+        self.contempt = limits.contempt;
+        self.skill = limits.skill.clone();
+        self.move_time_limit = limits
+            .move_time
+            .or(limits.time_manager.as_ref().map(|tm| tm.hard_limit()));
+        self.time_budget = limits
+            .move_time
+            .or(limits.time_manager.as_ref().map(|tm| tm.soft_limit()));
+        self.node_limit = limits.nodes;
+        self.nodes = 0;
+        self.seldepth = 0;
+        self.stats = SearchStats::default();
+        if let Some(tracer) = self.tracer.as_mut() {
+            tracer.reset();
+        }
+        self.shared_nodes.store(0, Ordering::Relaxed);
+        // Mate searches and positions without moves leave the helpers idle.
+        for helper in &mut self.helpers {
+            helper.stats = SearchStats::default();
+            helper.shared_nodes.store(0, Ordering::Relaxed);
+        }
+        self.stopped = false;
+        self.stop_requested = Arc::clone(stop_requested);
+        self.completed_depth = 0;
+        self.pv_lines.clear();
+    }
+
+    /// Iterative deepening over `root_moves`. Helpers perturb it so their
+    /// trees diverge from the main thread's: odd helpers search one ply
+    /// deeper and each starts from a different root move.
+    fn iterate(
+        &mut self,
+        board: &mut Board,
+        mut root_moves: Vec<ChessMove>,
+        max_depth: i32,
+        mut time_manager: Option<TimeManager>,
+    ) {
+        // A weakened search needs lines to choose among.
+        let multi_pv = match self.skill {
+            Some(_) => self.multi_pv.max(SKILL_MULTI_PV),
+            None => self.multi_pv,
+        }
+        .min(root_moves.len());
+        let depth_offset = (self.thread_id % 2) as i32;
+        let rotation = self.thread_id % root_moves.len();
+        root_moves.rotate_left(rotation);
+
+        let mut stable_iterations = 0;
+        for iteration in 1..=max_depth.max(1) {
+            let current_depth = (iteration + depth_offset).min(max_depth.max(1));
+            self.seldepth = 0;
+            if let Some(tracer) = self.tracer.as_mut() {
+                tracer.start_iteration();
+            }
+            let mut lines: Vec<PvLine> = Vec::with_capacity(multi_pv);
+            let mut aborted = false;
+
+            for pv_index in 0..multi_pv {
+                let excluded: Vec<ChessMove> = lines.iter().map(|l| l.moves[0]).collect();
+                let previous_score = self
+                    .pv_lines
+                    .get(pv_index)
+                    .map(|l| l.score)
+                    .filter(|s| current_depth >= ASPIRATION_MIN_DEPTH && s.abs() < MATE_BOUND);
+                let Some(line) = self.aspiration_search(
+                    board,
+                    current_depth,
+                    pv_index,
+                    &root_moves,
+                    &excluded,
+                    previous_score,
+                ) else {
+                    aborted = true;
+                    break;
+                };
+                if self.reports_line(pv_index) {
+                    self.report_line(current_depth, pv_index, &line, NodeType::PVNode);
+                }
+                lines.push(line);
+            }
+
+            // A partial iteration is only trusted when nothing better exists.
+            if aborted && !self.pv_lines.is_empty() {
+                break;
+            }
+            let previous = self.pv_lines.first().map(|l| (l.moves[0], l.score));
+            if !lines.is_empty() {
+                self.pv_lines = lines;
+            }
+            if aborted {
+                break;
+            }
+            let (best_move, score) = (self.pv_lines[0].moves[0], self.pv_lines[0].score);
+            match previous {
+                Some((mv, previous_score))
+                    if mv == best_move && score >= previous_score - EASY_MOVE_MAX_DROP =>
+                {
+                    stable_iterations += 1
+                }
+                _ => stable_iterations = 0,
+            }
+            self.completed_depth = current_depth;
+            if let Some(tracer) = self.tracer.as_mut() {
+                tracer.finish_iteration(current_depth);
+            }
+            if self.thread_id == 0 {
+                self.stats.depths.push(DepthStats {
+                    depth: current_depth,
+                    nodes: self.nodes(),
+                    time: self.search_start_time.elapsed(),
+                });
+            }
+
+            if let Some(tm) = time_manager.as_mut() {
+                // Pondering time is free, so keep iterating until the ponderhit.
+                tm.update(best_move, score);
+                if tm.score_dropped() {
+                    self.stats.score_drop_extensions += 1;
+                }
+                if !self.pondering() && tm.should_stop(self.clock_start.elapsed()) {
+                    break;
+                }
+            }
+            if stable_iterations >= EASY_MOVE_STABILITY
+                && self.is_easy_move(board, current_depth, &root_moves)
+            {
+                self.stats.early_stop = Some(EarlyStop::EasyMove);
+                break;
+            }
+            if current_depth >= max_depth {
+                break;
+            }
+
+            // Search the previous iteration's lines first next time around.
+            for (i, line) in self.pv_lines.iter().enumerate() {
+                if let Some(pos) = root_moves.iter().position(|m| *m == line.moves[0]) {
+                    root_moves[i..=pos].rotate_right(1);
+                }
+            }
+        }
+    }
+
+    /// Whether a timed main search can play its best move now: enough of
+    /// the budget is spent and no other move comes within the margin.
+    fn is_easy_move(&mut self, board: &mut Board, depth: i32, root_moves: &[ChessMove]) -> bool {
+        let Some(budget) = self.time_budget.filter(|_| self.thread_id == 0) else {
+            return false;
+        };
+        if self.pondering() || self.clock_start.elapsed() < budget.mul_f64(EASY_MOVE_TIME_FRACTION)
+        {
+            return false;
+        }
+        let best = &self.pv_lines[0];
+        let threshold = best.score - EASY_MOVE_MARGIN;
+        if let Some(second) = self.pv_lines.get(1) {
+            return second.score < threshold;
+        }
+        let excluded = [best.moves[0]];
+        self.search_root(
+            board,
+            (depth / 2).max(1),
+            root_moves,
+            &excluded,
+            threshold - NULL_WINDOW,
+            threshold,
+        )
+        .is_some_and(|(_, score)| score < threshold)
+    }
+
+    /// Searches the root in a window around `previous_score`, widening the
+    /// failing side until the score falls inside. Returns None when stopped.
+    fn aspiration_search(
+        &mut self,
+        board: &mut Board,
+        depth: i32,
+        pv_index: usize,
+        root_moves: &[ChessMove],
+        excluded: &[ChessMove],
+        previous_score: Option<f64>,
+    ) -> Option<PvLine> {
+        let mut delta = ASPIRATION_DELTA;
+        let (mut alpha, mut beta) = match previous_score {
+            Some(score) => (score - delta, score + delta),
+            None => (f64::NEG_INFINITY, f64::INFINITY),
+        };
+
+        loop {
+            let (mv, score) = self.search_root(board, depth, root_moves, excluded, alpha, beta)?;
+            let bound = if score <= alpha {
+                NodeType::AllNode
+            } else if score >= beta {
+                NodeType::CutNode
+            } else {
+                NodeType::PVNode
+            };
+            let line = PvLine {
+                score,
+                moves: self.extract_pv(board, mv, score, depth as usize),
+            };
+            if bound == NodeType::PVNode {
+                return Some(line);
+            }
+            if self.reports_line(pv_index) {
+                self.report_line(depth, pv_index, &line, bound);
+            }
+
+            delta *= 2.0;
+            let widened = if delta > ASPIRATION_MAX_DELTA {
+                None
+            } else {
+                Some(delta)
+            };
+            if bound == NodeType::AllNode {
+                alpha = widened.map_or(f64::NEG_INFINITY, |d| score - d);
+            } else {
+                beta = widened.map_or(f64::INFINITY, |d| score + d);
+            }
+        }
+    }
+
+    /// Picks the main line by weighing each thread's best move by its score
+    /// and completed depth.
+    fn vote(&mut self, helper_lines: Vec<(PvLine, i32)>) {
+        let Some(main_line) = self.pv_lines.first() else {
+            return;
+        };
+        let mut candidates = vec![(main_line.clone(), self.completed_depth)];
+        candidates.extend(helper_lines);
+
+        let min_score = candidates
+            .iter()
+            .map(|(line, _)| line.score)
+            .fold(f64::INFINITY, f64::min);
+        let mut votes: Vec<(ChessMove, f64)> = Vec::new();
+        for (line, depth) in &candidates {
+            let weight = (line.score - min_score + VOTE_SCORE_OFFSET) * *depth as f64;
+            match votes.iter_mut().find(|(mv, _)| *mv == line.moves[0]) {
+                Some((_, total)) => *total += weight,
+                None => votes.push((line.moves[0], weight)),
+            }
+        }
+        let winner = votes
+            .iter()
+            .fold(votes[0], |best, &v| if v.1 > best.1 { v } else { best })
+            .0;
+
+        if winner != self.pv_lines[0].moves[0] {
+            let (line, _) = candidates
+                .into_iter()
+                .filter(|(line, _)| line.moves[0] == winner)
+                .max_by_key(|(_, depth)| *depth)
+                .unwrap();
+            self.pv_lines[0] = line;
+        }
+    }
+
+    /// Searches `board` within `limits`. The best move is None only when
+    /// there is no legal move.
+    pub fn find_best_move(
+        &mut self,
+        board: &mut Board,
+        limits: &SearchLimits,
+        stop_requested: &Arc<AtomicBool>,
+    ) -> SearchResult {
+        let best_move = self.search_position(board, limits, stop_requested);
+
+        let mut stats = self.stats.clone();
+        for helper in &self.helpers {
+            stats.add_counters(&helper.stats);
+        }
+        stats.nodes = self.nodes();
+        let line = self.pv_lines.first();
+        SearchResult {
+            best_move,
+            ponder: self.ponder_move(),
+            score: line.map(|l| l.score),
+            pv: line.map_or_else(Vec::new, |l| l.moves.clone()),
+            depth: self.completed_depth,
+            stats,
+            trace: self.tracer.as_mut().and_then(|t| t.take_completed()),
+        }
+    }
+
+    fn search_position(
+        &mut self,
+        board: &mut Board,
+        limits: &SearchLimits,
+        stop_requested: &Arc<AtomicBool>,
+    ) -> Option<ChessMove> {
+        self.prepare(limits, stop_requested);
+        self.tt.new_search();
+
+        let mut root_moves = generate_legal_moves(board);
+        let legal_moves = root_moves.len();
+        if !limits.search_moves.is_empty() {
+            root_moves.retain(|m| limits.search_moves.contains(m));
+        }
+        if root_moves.is_empty() {
+            return None;
+        }
+
+        if let Some(max_moves) = limits.mate {
+            if let Some(line) = self.find_mate(board, &root_moves, max_moves) {
+                let best = line.moves[0];
+                self.pv_lines = vec![line];
+                return Some(best);
+            }
+            if !self.stopped {
+                self.observer
+                    .on_message(&format!("no mate in {} found", max_moves));
+            }
+            // Play the best move of a normal search instead. Like any
+            // search, it completes depth 1 even once stopped.
+            self.stopped = false;
+            self.completed_depth = 0;
+        }
+
+        // A forced move only needs a score to report. A single searchmoves
+        // move is analysed like any other.
+        if legal_moves == 1 && self.time_budget.is_some() {
+            self.stats.early_stop = Some(EarlyStop::SingleReply);
+            self.iterate(board, root_moves.clone(), 1, None);
+            return Some(root_moves[0]);
+        }
+
+        // Helpers search until the main thread, which owns the clock,
+        // finishes, or until all threads together use up the node limit.
+        let helper_stop = Arc::new(AtomicBool::new(false));
+        let helper_limits = SearchLimits {
+            nodes: limits.nodes,
+            history: limits.history.clone(),
+            contempt: limits.contempt,
+            ..SearchLimits::with_depth(limits.depth)
+        };
+        let mut helpers = std::mem::take(&mut self.helpers);
+        // Everyone starts together, so helpers are searching while the
+        // main thread is.
+        let start = Barrier::new(helpers.len() + 1);
+        let helper_lines: Vec<(PvLine, i32)> = thread::scope(|scope| {
+            let handles: Vec<_> = helpers
+                .iter_mut()
+                .map(|helper| {
+                    let mut helper_board = board.clone();
+                    let helper_moves = root_moves.clone();
+                    let (helper_limits, helper_stop, start) =
+                        (&helper_limits, &helper_stop, &start);
+                    scope.spawn(move || {
+                        helper.prepare(helper_limits, helper_stop);
+                        start.wait();
+                        helper.iterate(&mut helper_board, helper_moves, helper_limits.depth, None);
+                        helper.shared_nodes.store(helper.nodes, Ordering::Relaxed);
+                        helper
+                            .pv_lines
+                            .first()
+                            .map(|line| (line.clone(), helper.completed_depth))
+                    })
+                })
+                .collect();
+
+            start.wait();
+            self.iterate(
+                board,
+                root_moves.clone(),
+                limits.depth,
+                limits.time_manager.clone(),
+            );
+            helper_stop.store(true, Ordering::Relaxed);
+
+            handles
+                .into_iter()
+                .filter_map(|h| h.join().unwrap())
+                .collect()
+        });
+        self.helpers = helpers;
+
+        if let Some(skill) = self.skill.as_ref().filter(|_| !self.pv_lines.is_empty()) {
+            let picked = skill.pick(&self.pv_lines);
+            let line = self.pv_lines.remove(picked);
+            self.pv_lines.insert(0, line);
+        } else if self.multi_pv == 1 {
+            self.vote(helper_lines);
+        }
+
+        Some(
+            self.pv_lines
+                .first()
+                .map_or(root_moves[0], |line| line.moves[0]),
+        )
+    }
+}
+
+/// Mate-distance pruning: narrows the window to the scores still
+/// reachable from `ply`, since no line from here beats being mated now or
+/// mating next move. The node can return alpha if the window closes.
+fn mate_distance_window(alpha: f64, beta: f64, ply: usize) -> (f64, f64) {
+    (
+        alpha.max(-(MATE_SCORE - ply as f64)),
+        beta.min(MATE_SCORE - (ply + 1) as f64),
+    )
+}
+
+/// Mate scores count plies from the root; the table stores them counted
+/// from the node instead, so they stay right wherever the position recurs.
+fn value_to_tt(value: f64, ply: usize) -> f64 {
+    if value >= MATE_BOUND {
+        value + ply as f64
+    } else if value <= -MATE_BOUND {
+        value - ply as f64
+    } else {
+        value
+    }
+}
+
+fn value_from_tt(value: f64, ply: usize) -> f64 {
+    if value >= MATE_BOUND {
+        value - ply as f64
+    } else if value <= -MATE_BOUND {
+        value + ply as f64
+    } else {
+        value
+    }
+}
+
+pub fn move_to_uci(mv: &ChessMove) -> String {
+    use crate::movegen::{col_of, row_of};
+    let from_file = (col_of(mv.from) as u8 + b'a') as char;
+    let from_rank = (7 - row_of(mv.from)) as u8 + b'1';
+    let to_file = (col_of(mv.to) as u8 + b'a') as char;
+    let to_rank = (7 - row_of(mv.to)) as u8 + b'1';
+    let mut s = format!(
+        "{}{}{}{}",
+        from_file as char, from_rank as char, to_file as char, to_rank as char
+    );
+    if mv.promoted_piece != Piece::Empty {
+        let c = match mv.promoted_piece {
+            Piece::WQ | Piece::BQ => 'q',
+            Piece::WR | Piece::BR => 'r',
+            Piece::WN | Piece::BN => 'n',
+            Piece::WB | Piece::BB => 'b',
+            _ => '?',
+        };
+        s.push(c);
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    use crate::params::PositionParams;
+    use crate::position_utils::{
+        parse_uci_move, set_board_position, set_board_position_with_history,
+    };
+
+    #[test]
+    fn find_best_move_white_mate_in_one() {
+        let mut board = Board {
+            board: [Piece::Empty; 64],
+            white_to_move: true,
+            can_white_castle_kingside: false,
+            can_white_castle_queenside: false,
+            can_black_castle_kingside: false,
+            can_black_castle_queenside: false,
+            en_passant_square: -1,
+            half_move_capture_or_pawn_clock: 0,
+            full_move_number: 1,
+        };
+        let params = PositionParams {
+            is_fen: true,
+            position: "4k3/8/4K3/8/8/8/8/7R w - - 0 1".to_string(),
+            moves: vec![],
+        };
+        set_board_position(&mut board, &params).unwrap();
+
+        let mut search = Search::new();
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let best_move = search
+            .find_best_move(&mut board, &SearchLimits::with_depth(2), &stop)
+            .best_move;
+        assert!(best_move.is_some());
+        assert_eq!(move_to_uci(&best_move.unwrap()), "h1h8");
+    }
+
+    #[test]
+    fn find_best_move_black_mate_in_one() {
+        let mut board = Board {
+            board: [Piece::Empty; 64],
+            white_to_move: false,
+            can_white_castle_kingside: false,
+            can_white_castle_queenside: false,
+            can_black_castle_kingside: false,
+            can_black_castle_queenside: false,
+            en_passant_square: -1,
+            half_move_capture_or_pawn_clock: 0,
+            full_move_number: 1,
+        };
+        let params = PositionParams {
+            is_fen: true,
+            position: "7r/8/8/8/8/4k3/8/4K3 b - - 0 1".to_string(),
+            moves: vec![],
+        };
+        set_board_position(&mut board, &params).unwrap();
+
+        let mut search = Search::new();
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let best_move = search
+            .find_best_move(&mut board, &SearchLimits::with_depth(2), &stop)
+            .best_move;
+        assert!(best_move.is_some());
+        assert_eq!(move_to_uci(&best_move.unwrap()), "h8h1");
+    }
+
+    #[test]
+    fn find_best_move_hanging_queen() {
+        let mut board = Board {
+            board: [Piece::Empty; 64],
+            white_to_move: true,
+            can_white_castle_kingside: false,
+            can_white_castle_queenside: false,
+            can_black_castle_kingside: false,
+            can_black_castle_queenside: false,
+            en_passant_square: -1,
+            half_move_capture_or_pawn_clock: 0,
+            full_move_number: 1,
+        };
+        let params = PositionParams {
+            is_fen: true,
+            position: "rnb1kbnr/pppp1ppp/8/4p1q1/4P3/5N2/PPPP1PPP/RNBQKB1R w - - 0 1".to_string(),
+            moves: vec![],
+        };
+        set_board_position(&mut board, &params).unwrap();
+
+        let mut search = Search::new();
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let best_move = search
+            .find_best_move(&mut board, &SearchLimits::with_depth(2), &stop)
+            .best_move;
+        assert!(best_move.is_some());
+        assert_eq!(move_to_uci(&best_move.unwrap()), "f3g5");
+    }
+
+    #[test]
+    fn find_best_move_knight_fork() {
+        let mut board = Board {
+            board: [Piece::Empty; 64],
+            white_to_move: true,
+            can_white_castle_kingside: false,
+            can_white_castle_queenside: false,
+            can_black_castle_kingside: false,
+            can_black_castle_queenside: false,
+            en_passant_square: -1,
+            half_move_capture_or_pawn_clock: 0,
+            full_move_number: 1,
+        };
+        let params = PositionParams {
+            is_fen: true,
+            position: "8/4k3/7q/8/8/4N3/4K3/4R3 w - - 0 1".to_string(),
+            moves: vec![],
+        };
+        set_board_position(&mut board, &params).unwrap();
+
+        let mut search = Search::new();
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let best_move = search
+            .find_best_move(&mut board, &SearchLimits::with_depth(4), &stop)
+            .best_move;
+        assert!(best_move.is_some());
+        assert_eq!(move_to_uci(&best_move.unwrap()), "e3f5");
+    }
+
+    #[test]
+    fn find_best_move_queen_sac_smothered_mate() {
+        let mut board = Board {
+            board: [Piece::Empty; 64],
+            white_to_move: true,
+            can_white_castle_kingside: false,
+            can_white_castle_queenside: false,
+            can_black_castle_kingside: false,
+            can_black_castle_queenside: false,
+            en_passant_square: -1,
+            half_move_capture_or_pawn_clock: 0,
+            full_move_number: 1,
+        };
+        let params = PositionParams {
+            is_fen: true,
+            position: "r6k/1p1b1Qbp/1n2B1pN/p7/Pq6/8/1P4PP/R6K w - - 1 27".to_string(),
+            moves: vec![],
+        };
+        set_board_position(&mut board, &params).unwrap();
+
+        let mut search = Search::new();
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let best_move = search
+            .find_best_move(&mut board, &SearchLimits::with_depth(4), &stop)
+            .best_move;
+        assert!(best_move.is_some());
+        assert_eq!(move_to_uci(&best_move.unwrap()), "f7g8");
+    }
+
+    #[test]
+    fn find_best_move_check_extension_sees_mate_past_horizon() {
+        let mut board = Board {
+            board: [Piece::Empty; 64],
+            white_to_move: true,
+            can_white_castle_kingside: false,
+            can_white_castle_queenside: false,
+            can_black_castle_kingside: false,
+            can_black_castle_queenside: false,
+            en_passant_square: -1,
+            half_move_capture_or_pawn_clock: 0,
+            full_move_number: 1,
+        };
+        let params = PositionParams {
+            is_fen: true,
+            position: "r1b3kr/ppp1Bp1p/1b6/n2P4/2p3q1/2Q2N2/P4PPP/RN2R1K1 w - - 1 1".to_string(),
+            moves: vec![],
+        };
+        set_board_position(&mut board, &params).unwrap();
+
+        let mut search = Search::new();
+        let stop = Arc::new(AtomicBool::new(false));
+
+        // Qxh8+ Kxh8 Bf6+ Kg8 Re8# is five plies; the two checks are
+        // extended so four suffice.
+        let best_move = search
+            .find_best_move(&mut board, &SearchLimits::with_depth(4), &stop)
+            .best_move;
+        assert_eq!(move_to_uci(&best_move.unwrap()), "c3h8");
+        // Scored by distance: mate lands on the fifth ply.
+        assert_eq!(search.pv_lines()[0].score, MATE_SCORE - 5.0);
+        assert!(search.seldepth >= 5);
+    }
+
+    #[test]
+    fn mate_scores_are_stored_relative_to_the_node() {
+        // Mate in three from a node at ply 4 is mate at ply 7 from the root.
+        let stored = value_to_tt(MATE_SCORE - 7.0, 4);
+        assert_eq!(stored, MATE_SCORE - 3.0);
+        assert_eq!(value_from_tt(stored, 2), MATE_SCORE - 5.0);
+        assert_eq!(
+            value_from_tt(value_to_tt(-(MATE_SCORE - 6.0), 6), 1),
+            -(MATE_SCORE - 1.0)
+        );
+        assert_eq!(value_to_tt(1.5, 10), 1.5);
+    }
+
+    #[test]
+    fn find_best_move_quiescence_sees_recapture() {
+        let mut board = Board {
+            board: [Piece::Empty; 64],
+            white_to_move: true,
+            can_white_castle_kingside: false,
+            can_white_castle_queenside: false,
+            can_black_castle_kingside: false,
+            can_black_castle_queenside: false,
+            en_passant_square: -1,
+            half_move_capture_or_pawn_clock: 0,
+            full_move_number: 1,
+        };
+        let params = PositionParams {
+            is_fen: true,
+            position: "4k3/8/3p4/4p3/8/8/4Q3/4K3 w - - 0 1".to_string(),
+            moves: vec![],
+        };
+        set_board_position(&mut board, &params).unwrap();
+
+        let mut search = Search::new();
+        let stop = Arc::new(AtomicBool::new(false));
+
+        // At depth one Qxe5+ wins a pawn, until the pawn recapture is seen.
+        let best_move = search
+            .find_best_move(&mut board, &SearchLimits::with_depth(1), &stop)
+            .best_move;
+        assert_ne!(move_to_uci(&best_move.unwrap()), "e2e5");
+        assert!((search.pv_lines()[0].score - 7.0).abs() < 0.5);
+    }
+
+    #[test]
+    fn find_best_move_opening_hanging_bishop() {
+        let mut board = Board {
+            board: [Piece::Empty; 64],
+            white_to_move: false,
+            can_white_castle_kingside: true,
+            can_white_castle_queenside: true,
+            can_black_castle_kingside: true,
+            can_black_castle_queenside: true,
+            en_passant_square: -1,
+            half_move_capture_or_pawn_clock: 0,
+            full_move_number: 1,
+        };
+        let params = PositionParams {
+            is_fen: true,
+            position: "rnb1kbnr/ppqppppp/2p5/1B6/3PP3/2P5/PP3PPP/RNBQK1NR b KQkq - 0 1".to_string(),
+            moves: vec![],
+        };
+        set_board_position(&mut board, &params).unwrap();
+
+        let mut search = Search::new();
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let best_move = search
+            .find_best_move(&mut board, &SearchLimits::with_depth(3), &stop)
+            .best_move;
+        assert!(best_move.is_some());
+        assert_eq!(move_to_uci(&best_move.unwrap()), "c6b5");
+    }
+
+    #[test]
+    fn find_best_move_multi_pv_distinct_lines() {
+        let mut board = Board {
+            board: [Piece::Empty; 64],
+            white_to_move: true,
+            can_white_castle_kingside: false,
+            can_white_castle_queenside: false,
+            can_black_castle_kingside: false,
+            can_black_castle_queenside: false,
+            en_passant_square: -1,
+            half_move_capture_or_pawn_clock: 0,
+            full_move_number: 1,
+        };
+        let params = PositionParams {
+            is_fen: true,
+            position: "rnb1kbnr/pppp1ppp/8/4p1q1/4P3/5N2/PPPP1PPP/RNBQKB1R w - - 0 1".to_string(),
+            moves: vec![],
+        };
+        set_board_position(&mut board, &params).unwrap();
+
+        let mut search = Search::new();
+        search.set_multi_pv(3);
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let best_move = search
+            .find_best_move(&mut board, &SearchLimits::with_depth(2), &stop)
+            .best_move;
+        assert_eq!(move_to_uci(&best_move.unwrap()), "f3g5");
+
+        let lines = search.pv_lines();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].moves[0], best_move.unwrap());
+        for i in 1..lines.len() {
+            assert!(lines[i - 1].score >= lines[i].score);
+            for j in 0..i {
+                assert_ne!(lines[i].moves[0], lines[j].moves[0]);
+            }
+        }
+    }
+
+    #[test]
+    fn find_best_move_respects_search_moves() {
+        let mut board = Board {
+            board: [Piece::Empty; 64],
+            white_to_move: true,
+            can_white_castle_kingside: false,
+            can_white_castle_queenside: false,
+            can_black_castle_kingside: false,
+            can_black_castle_queenside: false,
+            en_passant_square: -1,
+            half_move_capture_or_pawn_clock: 0,
+            full_move_number: 1,
+        };
+        let params = PositionParams {
+            is_fen: true,
+            position: "rnb1kbnr/pppp1ppp/8/4p1q1/4P3/5N2/PPPP1PPP/RNBQKB1R w - - 0 1".to_string(),
+            moves: vec![],
+        };
+        set_board_position(&mut board, &params).unwrap();
+
+        let limits = SearchLimits {
+            depth: 2,
+            search_moves: vec![
+                parse_uci_move(&board, "d2d3").unwrap(),
+                parse_uci_move(&board, "b1c3").unwrap(),
+            ],
+            ..Default::default()
+        };
+        let mut search = Search::new();
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let best_move = search.find_best_move(&mut board, &limits, &stop).best_move;
+        assert!(best_move.is_some());
+        assert!(limits.search_moves.contains(&best_move.unwrap()));
+    }
+
+    #[test]
+    fn find_best_move_node_limit_is_deterministic() {
+        // No forced mate, which would let the search finish under the limit.
+        let params = PositionParams {
+            is_fen: true,
+            position: "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1"
+                .to_string(),
+            moves: vec![],
+        };
+        let limits = SearchLimits {
+            depth: MAX_DEPTH as i32 - 1,
+            nodes: Some(20_000),
+            ..Default::default()
+        };
+
+        let mut results = Vec::new();
+        for _ in 0..2 {
+            let mut board = Board {
+                board: [Piece::Empty; 64],
+                white_to_move: true,
+                can_white_castle_kingside: false,
+                can_white_castle_queenside: false,
+                can_black_castle_kingside: false,
+                can_black_castle_queenside: false,
+                en_passant_square: -1,
+                half_move_capture_or_pawn_clock: 0,
+                full_move_number: 1,
+            };
+            set_board_position(&mut board, &params).unwrap();
+
+            let mut search = Search::new();
+            let stop = Arc::new(AtomicBool::new(false));
+            let best_move = search.find_best_move(&mut board, &limits, &stop).best_move;
+            assert_eq!(search.nodes(), 20_000);
+            results.push((best_move, search.pv_lines()[0].score));
+        }
+        assert_eq!(results[0], results[1]);
+    }
+
+    #[test]
+    fn find_best_move_mate_in_two_with_pv() {
+        let mut board = Board {
+            board: [Piece::Empty; 64],
+            white_to_move: true,
+            can_white_castle_kingside: true,
+            can_white_castle_queenside: true,
+            can_black_castle_kingside: true,
+            can_black_castle_queenside: true,
+            en_passant_square: -1,
+            half_move_capture_or_pawn_clock: 0,
+            full_move_number: 1,
+        };
+        let params = PositionParams {
+            is_fen: true,
+            position: "r2qkb1r/pp2nppp/3p4/2pNN1B1/2BnP3/3P4/PPP2PPP/R2bK2R w KQkq - 1 1"
+                .to_string(),
+            moves: vec![],
+        };
+        set_board_position(&mut board, &params).unwrap();
+
+        let limits = SearchLimits {
+            mate: Some(3),
+            ..Default::default()
+        };
         let mut search = Search::new();
-        let stop = AtomicBool::new(false);
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let best_move = search.find_best_move(&mut board, &limits, &stop).best_move;
+        assert_eq!(move_to_uci(&best_move.unwrap()), "d5f6");
+        let pv: Vec<String> = search.pv_lines()[0].moves.iter().map(move_to_uci).collect();
+        assert_eq!(pv, vec!["d5f6", "g7f6", "c4f7"]);
+        // Reported alongside the line: the mated position is three plies in.
+        assert_eq!(search.seldepth, 3);
+    }
 
-        let best_move = search.find_best_move(&mut board, 2, &stop, None);
-        assert!(best_move.is_some());
-        assert_eq!(move_to_uci(&best_move.unwrap()), "h1h8");
+    #[test]
+    fn find_best_move_reports_no_mate() {
+        let mut board = Board {
+            board: [Piece::Empty; 64],
+            white_to_move: true,
+            can_white_castle_kingside: false,
+            can_white_castle_queenside: false,
+            can_black_castle_kingside: false,
+            can_black_castle_queenside: false,
+            en_passant_square: -1,
+            half_move_capture_or_pawn_clock: 0,
+            full_move_number: 1,
+        };
+        let params = PositionParams {
+            is_fen: true,
+            position: "8/4k3/7q/8/8/4N3/4K3/4R3 w - - 0 1".to_string(),
+            moves: vec![],
+        };
+        set_board_position(&mut board, &params).unwrap();
+
+        let limits = SearchLimits {
+            mate: Some(2),
+            ..SearchLimits::with_depth(4)
+        };
+        let mut search = Search::new();
+        let observer = Arc::new(RecordingObserver::default());
+        search.set_observer(observer.clone());
+        let stop = Arc::new(AtomicBool::new(false));
+
+        // No mate, so a normal search finds the fork that wins the queen.
+        let result = search.find_best_move(&mut board, &limits, &stop);
+        assert_eq!(move_to_uci(&result.best_move.unwrap()), "e3f5");
+        assert!(result.score.is_some_and(|score| score.abs() < MATE_BOUND));
+        assert_eq!(result.depth, 4);
+        // Each mate depth searched is reported before the normal search.
+        let depths = observer.depths.lock().unwrap();
+        assert_eq!(depths[..3], [1, 3, 1]);
     }
 
     #[test]
-    fn find_best_move_black_mate_in_one() {
+    fn find_best_move_ignores_clock_while_pondering() {
         let mut board = Board {
             board: [Piece::Empty; 64],
-            white_to_move: false,
+            white_to_move: true,
             can_white_castle_kingside: false,
             can_white_castle_queenside: false,
             can_black_castle_kingside: false,
@@ -438,21 +2431,33 @@ mod tests {
         };
         let params = PositionParams {
             is_fen: true,
-            position: "7r/8/8/8/8/4k3/8/4K3 b - - 0 1".to_string(),
+            position: "8/4k3/7q/8/8/4N3/4K3/4R3 w - - 0 1".to_string(),
             moves: vec![],
         };
         set_board_position(&mut board, &params).unwrap();
 
+        let mut limits = SearchLimits {
+            depth: 3,
+            move_time: Some(Duration::ZERO),
+            ..Default::default()
+        };
+        let stop = Arc::new(AtomicBool::new(false));
+
+        // Out of time from the start: only the first iteration is searched.
         let mut search = Search::new();
-        let stop = AtomicBool::new(false);
+        let result = search.find_best_move(&mut board, &limits, &stop);
+        assert_eq!(result.depth, 1);
+        assert_eq!(search.pv_lines()[0].moves.len(), 1);
 
-        let best_move = search.find_best_move(&mut board, 2, &stop, None);
-        assert!(best_move.is_some());
-        assert_eq!(move_to_uci(&best_move.unwrap()), "h8h1");
+        limits.ponder = Some(Arc::new(AtomicBool::new(true)));
+        let mut search = Search::new();
+        search.find_best_move(&mut board, &limits, &stop);
+        assert_eq!(search.pv_lines()[0].moves.len(), 3);
+        assert!(search.ponder_move().is_some());
     }
 
     #[test]
-    fn find_best_move_hanging_queen() {
+    fn find_best_move_reuses_state_until_cleared() {
         let mut board = Board {
             board: [Piece::Empty; 64],
             white_to_move: true,
@@ -466,21 +2471,27 @@ mod tests {
         };
         let params = PositionParams {
             is_fen: true,
-            position: "rnb1kbnr/pppp1ppp/8/4p1q1/4P3/5N2/PPPP1PPP/RNBQKB1R w - - 0 1".to_string(),
+            position: "8/4k3/7q/8/8/4N3/4K3/4R3 w - - 0 1".to_string(),
             moves: vec![],
         };
         set_board_position(&mut board, &params).unwrap();
 
+        let limits = SearchLimits::with_depth(4);
         let mut search = Search::new();
-        let stop = AtomicBool::new(false);
+        let stop = Arc::new(AtomicBool::new(false));
 
-        let best_move = search.find_best_move(&mut board, 2, &stop, None);
-        assert!(best_move.is_some());
-        assert_eq!(move_to_uci(&best_move.unwrap()), "f3g5");
+        search.find_best_move(&mut board, &limits, &stop);
+        let cold_nodes = search.nodes();
+        search.find_best_move(&mut board, &limits, &stop);
+        assert!(search.nodes() < cold_nodes);
+
+        search.clear();
+        search.find_best_move(&mut board, &limits, &stop);
+        assert_eq!(search.nodes(), cold_nodes);
     }
 
     #[test]
-    fn find_best_move_knight_fork() {
+    fn find_best_move_with_helper_threads() {
         let mut board = Board {
             board: [Piece::Empty; 64],
             white_to_move: true,
@@ -500,15 +2511,22 @@ mod tests {
         set_board_position(&mut board, &params).unwrap();
 
         let mut search = Search::new();
-        let stop = AtomicBool::new(false);
+        search.set_threads(3);
+        let stop = Arc::new(AtomicBool::new(false));
 
-        let best_move = search.find_best_move(&mut board, 4, &stop, None);
-        assert!(best_move.is_some());
+        let best_move = search
+            .find_best_move(&mut board, &SearchLimits::with_depth(4), &stop)
+            .best_move;
         assert_eq!(move_to_uci(&best_move.unwrap()), "e3f5");
+        // However quickly the main thread finishes, every helper searches.
+        assert!(search
+            .other_nodes
+            .iter()
+            .all(|n| n.load(Ordering::Relaxed) > 0));
     }
 
     #[test]
-    fn find_best_move_queen_sac_smothered_mate() {
+    fn find_best_move_node_limit_counts_all_threads() {
         let mut board = Board {
             board: [Piece::Empty; 64],
             white_to_move: true,
@@ -522,44 +2540,480 @@ mod tests {
         };
         let params = PositionParams {
             is_fen: true,
-            position: "r6k/1p1b1Qbp/1n2B1pN/p7/Pq6/8/1P4PP/R6K w - - 1 27".to_string(),
+            position: "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1"
+                .to_string(),
             moves: vec![],
         };
         set_board_position(&mut board, &params).unwrap();
 
         let mut search = Search::new();
-        let stop = AtomicBool::new(false);
+        search.set_threads(4);
+        let stop = Arc::new(AtomicBool::new(false));
+        let limits = SearchLimits {
+            depth: MAX_DEPTH as i32 - 1,
+            nodes: Some(5000),
+            ..Default::default()
+        };
 
-        let best_move = search.find_best_move(&mut board, 4, &stop, None);
-        assert!(best_move.is_some());
-        assert_eq!(move_to_uci(&best_move.unwrap()), "f7g8");
+        let result = search.find_best_move(&mut board, &limits, &stop);
+        assert!(result.best_move.is_some());
+        // Each thread may pass the limit by a node it counted concurrently.
+        assert!((5000..5000 + 4 * 2).contains(&result.stats.nodes));
+    }
+
+    /// Keeps the depth and MultiPV index of every line reported.
+    #[derive(Default)]
+    struct RecordingObserver {
+        depths: Mutex<Vec<i32>>,
+        multipvs: Mutex<Vec<i32>>,
+    }
+
+    impl SearchObserver for RecordingObserver {
+        fn on_info(&self, info: &SearchInfo) {
+            self.depths.lock().unwrap().push(info.depth.unwrap());
+            self.multipvs.lock().unwrap().extend(info.multipv);
+        }
+        fn on_message(&self, _message: &str) {}
+        fn on_result(&self, _result: &SearchResult) {}
     }
 
     #[test]
-    fn find_best_move_opening_hanging_bishop() {
+    fn find_best_move_returns_result_and_reports_to_observer() {
         let mut board = Board {
             board: [Piece::Empty; 64],
-            white_to_move: false,
-            can_white_castle_kingside: true,
-            can_white_castle_queenside: true,
-            can_black_castle_kingside: true,
-            can_black_castle_queenside: true,
+            white_to_move: true,
+            can_white_castle_kingside: false,
+            can_white_castle_queenside: false,
+            can_black_castle_kingside: false,
+            can_black_castle_queenside: false,
             en_passant_square: -1,
             half_move_capture_or_pawn_clock: 0,
             full_move_number: 1,
         };
         let params = PositionParams {
             is_fen: true,
-            position: "rnb1kbnr/ppqppppp/2p5/1B6/3PP3/2P5/PP3PPP/RNBQK1NR b KQkq - 0 1".to_string(),
+            position: "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1"
+                .to_string(),
             moves: vec![],
         };
         set_board_position(&mut board, &params).unwrap();
 
         let mut search = Search::new();
-        let stop = AtomicBool::new(false);
+        let observer = Arc::new(RecordingObserver::default());
+        search.set_observer(observer.clone());
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let result = search.find_best_move(&mut board, &SearchLimits::with_depth(4), &stop);
+        assert_eq!(result.depth, 4);
+        assert_eq!(result.best_move, Some(result.pv[0]));
+        assert_eq!(result.ponder, Some(result.pv[1]));
+        assert_eq!(result.score, Some(search.pv_lines()[0].score));
+        assert_eq!(*observer.depths.lock().unwrap(), vec![1, 2, 3, 4]);
+
+        let stats = result.stats;
+        assert_eq!(stats.nodes, search.nodes());
+        assert!(stats.qnodes > 0 && stats.qnodes < stats.nodes);
+        assert!(stats.tt_cutoffs > 0 && stats.tt_cutoffs <= stats.tt_hits);
+        assert!(stats.first_move_cutoff_rate().is_some_and(|r| r > 0.5));
+
+        let depths: Vec<i32> = stats.depths.iter().map(|d| d.depth).collect();
+        assert_eq!(depths, vec![1, 2, 3, 4]);
+        assert!(stats
+            .depths
+            .windows(2)
+            .all(|w| w[0].nodes < w[1].nodes && w[0].time <= w[1].time));
+        assert_eq!(stats.depths[3].nodes, stats.nodes);
+        assert!(stats
+            .effective_branching_factor()
+            .is_some_and(|ebf| ebf > 1.0));
+    }
 
-        let best_move = search.find_best_move(&mut board, 3, &stop, None);
-        assert!(best_move.is_some());
-        assert_eq!(move_to_uci(&best_move.unwrap()), "c6b5");
+    #[test]
+    fn aspiration_search_widens_to_full_window_score() {
+        let mut board = Board {
+            board: [Piece::Empty; 64],
+            white_to_move: true,
+            can_white_castle_kingside: false,
+            can_white_castle_queenside: false,
+            can_black_castle_kingside: false,
+            can_black_castle_queenside: false,
+            en_passant_square: -1,
+            half_move_capture_or_pawn_clock: 0,
+            full_move_number: 1,
+        };
+        let params = PositionParams {
+            is_fen: true,
+            position: "rnb1kbnr/pppp1ppp/8/4p1q1/4P3/5N2/PPPP1PPP/RNBQKB1R w - - 0 1".to_string(),
+            moves: vec![],
+        };
+        set_board_position(&mut board, &params).unwrap();
+        let root_moves = generate_legal_moves(&board);
+        let stop = Arc::new(AtomicBool::new(false));
+        let limits = SearchLimits::with_depth(3);
+
+        let mut search = Search::new();
+        search.prepare(&limits, &stop);
+        let (full_move, full_score) = search
+            .search_root(
+                &mut board,
+                3,
+                &root_moves,
+                &[],
+                f64::NEG_INFINITY,
+                f64::INFINITY,
+            )
+            .unwrap();
+
+        let mut search = Search::new();
+        search.prepare(&limits, &stop);
+        let (_, score) = search
+            .search_root(
+                &mut board,
+                3,
+                &root_moves,
+                &[],
+                full_score - 1.0,
+                full_score - 0.5,
+            )
+            .unwrap();
+        assert!(score >= full_score - 0.5);
+
+        // Starting far too low fails high until the window contains the score.
+        let mut search = Search::new();
+        search.prepare(&limits, &stop);
+        let line = search
+            .aspiration_search(&mut board, 3, 0, &root_moves, &[], Some(full_score - 3.0))
+            .unwrap();
+        assert_eq!(line.moves[0], full_move);
+        assert!((line.score - full_score).abs() < 1e-9);
+    }
+
+    #[test]
+    fn find_best_move_contempt_decides_repetitions() {
+        // Ng8 repeats the starting position a second time.
+        let params = PositionParams {
+            is_fen: false,
+            position: String::new(),
+            moves: ["g1f3", "g8f6", "f3g1", "f6g8", "g1f3", "g8f6", "f3g1"]
+                .iter()
+                .map(|m| m.to_string())
+                .collect(),
+        };
+        let mut board = Board {
+            board: [Piece::Empty; 64],
+            white_to_move: true,
+            can_white_castle_kingside: false,
+            can_white_castle_queenside: false,
+            can_black_castle_kingside: false,
+            can_black_castle_queenside: false,
+            en_passant_square: -1,
+            half_move_capture_or_pawn_clock: 0,
+            full_move_number: 1,
+        };
+        let history = set_board_position_with_history(&mut board, &params).unwrap();
+        let stop = Arc::new(AtomicBool::new(false));
+
+        for (contempt, repeats) in [(-50, true), (50, false)] {
+            let limits = SearchLimits {
+                history: history.clone(),
+                contempt,
+                ..SearchLimits::with_depth(3)
+            };
+            let result = Search::new().find_best_move(&mut board, &limits, &stop);
+            assert_eq!(move_to_uci(&result.best_move.unwrap()) == "f6g8", repeats);
+            if repeats {
+                assert_eq!(result.score, Some(0.5));
+            }
+        }
+    }
+
+    #[test]
+    fn find_best_move_with_skill_plays_a_near_best_line() {
+        let mut board = Board {
+            board: [Piece::Empty; 64],
+            white_to_move: true,
+            can_white_castle_kingside: false,
+            can_white_castle_queenside: false,
+            can_black_castle_kingside: false,
+            can_black_castle_queenside: false,
+            en_passant_square: -1,
+            half_move_capture_or_pawn_clock: 0,
+            full_move_number: 1,
+        };
+        let params = PositionParams {
+            is_fen: true,
+            position: "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1"
+                .to_string(),
+            moves: vec![],
+        };
+        set_board_position(&mut board, &params).unwrap();
+        let stop = Arc::new(AtomicBool::new(false));
+
+        for seed in 0..4 {
+            let limits = SearchLimits {
+                skill: Skill::from_level(0, seed),
+                ..SearchLimits::with_depth(3)
+            };
+            let mut search = Search::new();
+            let observer = Arc::new(RecordingObserver::default());
+            search.set_observer(observer.clone());
+            let result = search.find_best_move(&mut board, &limits, &stop);
+            let lines = search.pv_lines();
+            assert_eq!(lines.len(), SKILL_MULTI_PV);
+            // The extra lines are searched but, with MultiPV 1, not reported.
+            assert!(observer.multipvs.lock().unwrap().iter().all(|&k| k == 1));
+            assert_eq!(result.best_move, Some(lines[0].moves[0]));
+            assert_eq!(result.score, Some(lines[0].score));
+            let best = lines.iter().map(|l| l.score).fold(f64::MIN, f64::max);
+            assert!(lines[0].score > best - 1.2);
+        }
+    }
+
+    #[test]
+    fn find_best_move_plays_a_single_reply_at_once() {
+        let mut board = Board {
+            board: [Piece::Empty; 64],
+            white_to_move: true,
+            can_white_castle_kingside: false,
+            can_white_castle_queenside: false,
+            can_black_castle_kingside: false,
+            can_black_castle_queenside: false,
+            en_passant_square: -1,
+            half_move_capture_or_pawn_clock: 0,
+            full_move_number: 1,
+        };
+        // The queen on d1 checks; only Kxd1 is legal.
+        let params = PositionParams {
+            is_fen: true,
+            position: "rnb1kbnr/ppp2ppp/8/4P3/2p1P3/8/PP3PPP/RNBqKBNR w KQkq - 0 5".to_string(),
+            moves: vec![],
+        };
+        set_board_position(&mut board, &params).unwrap();
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let limits = SearchLimits {
+            depth: MAX_DEPTH as i32 - 1,
+            move_time: Some(Duration::from_secs(10)),
+            ..Default::default()
+        };
+        let result = Search::new().find_best_move(&mut board, &limits, &stop);
+        assert_eq!(move_to_uci(&result.best_move.unwrap()), "e1d1");
+        assert_eq!(result.depth, 1);
+        assert!(result.score.is_some());
+        assert_eq!(result.stats.early_stop, Some(EarlyStop::SingleReply));
+
+        // Without a time limit the depth is searched in full.
+        let result = Search::new().find_best_move(&mut board, &SearchLimits::with_depth(3), &stop);
+        assert_eq!(result.depth, 3);
+        assert_eq!(result.stats.early_stop, None);
+
+        // Nor is one move picked by searchmoves a forced move.
+        let startpos = PositionParams {
+            is_fen: false,
+            position: String::new(),
+            moves: vec![],
+        };
+        set_board_position(&mut board, &startpos).unwrap();
+        let limits = SearchLimits {
+            depth: 3,
+            move_time: Some(Duration::from_secs(10)),
+            search_moves: vec![parse_uci_move(&board, "e2e4").unwrap()],
+            ..Default::default()
+        };
+        let result = Search::new().find_best_move(&mut board, &limits, &stop);
+        assert_eq!(move_to_uci(&result.best_move.unwrap()), "e2e4");
+        assert_eq!(result.depth, 3);
+        assert_eq!(result.stats.early_stop, None);
+    }
+
+    #[test]
+    fn find_best_move_stops_early_on_easy_recapture() {
+        let mut board = Board {
+            board: [Piece::Empty; 64],
+            white_to_move: true,
+            can_white_castle_kingside: false,
+            can_white_castle_queenside: false,
+            can_black_castle_kingside: false,
+            can_black_castle_queenside: false,
+            en_passant_square: -1,
+            half_move_capture_or_pawn_clock: 0,
+            full_move_number: 1,
+        };
+        let params = PositionParams {
+            is_fen: true,
+            position: "rnbqkbnr/ppp1pppp/8/3N4/8/8/PPPPPPPP/R1BQKBNR b KQkq - 0 3".to_string(),
+            moves: vec![],
+        };
+        set_board_position(&mut board, &params).unwrap();
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let move_time = Duration::from_secs(20);
+        let limits = SearchLimits {
+            depth: MAX_DEPTH as i32 - 1,
+            move_time: Some(move_time),
+            ..Default::default()
+        };
+        let result = Search::new().find_best_move(&mut board, &limits, &stop);
+        assert_eq!(move_to_uci(&result.best_move.unwrap()), "d8d5");
+        assert_eq!(result.stats.early_stop, Some(EarlyStop::EasyMove));
+        let spent = result.stats.depths.last().unwrap().time;
+        assert!(spent >= move_time.mul_f64(EASY_MOVE_TIME_FRACTION));
+        assert!(spent < move_time / 2);
+    }
+
+    #[test]
+    fn find_best_move_traces_last_iteration_when_asked() {
+        let mut board = Board {
+            board: [Piece::Empty; 64],
+            white_to_move: true,
+            can_white_castle_kingside: false,
+            can_white_castle_queenside: false,
+            can_black_castle_kingside: false,
+            can_black_castle_queenside: false,
+            en_passant_square: -1,
+            half_move_capture_or_pawn_clock: 0,
+            full_move_number: 1,
+        };
+        let params = PositionParams {
+            is_fen: true,
+            position: "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1"
+                .to_string(),
+            moves: vec![],
+        };
+        set_board_position(&mut board, &params).unwrap();
+        let stop = Arc::new(AtomicBool::new(false));
+        let limits = SearchLimits::with_depth(6);
+
+        let mut search = Search::new();
+        let untraced = search.find_best_move(&mut board, &limits, &stop);
+        assert!(untraced.trace.is_none());
+
+        search.clear();
+        search.set_trace_depth(Some(2));
+        let result = search.find_best_move(&mut board, &limits, &stop);
+        // Tracing only records; the search itself is unchanged.
+        assert_eq!(result.stats.nodes, untraced.stats.nodes);
+        let trace = result.trace.unwrap();
+        assert_eq!(trace.depth, 6);
+        let root = &trace.nodes[0];
+        assert_eq!((root.parent, root.ply, root.mv), (None, 0, None));
+        assert!(trace.nodes.iter().all(|n| n.ply <= 2));
+        assert!(trace.nodes.iter().skip(1).all(|n| n.parent.is_some()));
+        // The main line's first move was searched from the last root.
+        let last_root = trace
+            .nodes
+            .iter()
+            .rposition(|n| n.parent.is_none())
+            .unwrap();
+        assert!(trace.nodes.iter().any(|n| {
+            n.parent == Some(last_root) && n.mv == result.best_move && n.score.is_some()
+        }));
+        assert!(trace.nodes.iter().any(|n| n.decision.is_some()));
+    }
+
+    #[test]
+    fn find_best_move_survives_every_param_at_its_minimum() {
+        let mut board = Board {
+            board: [Piece::Empty; 64],
+            white_to_move: true,
+            can_white_castle_kingside: false,
+            can_white_castle_queenside: false,
+            can_black_castle_kingside: false,
+            can_black_castle_queenside: false,
+            en_passant_square: -1,
+            half_move_capture_or_pawn_clock: 0,
+            full_move_number: 1,
+        };
+        let params = PositionParams {
+            is_fen: false,
+            position: String::new(),
+            moves: vec![],
+        };
+        set_board_position(&mut board, &params).unwrap();
+        let stop = Arc::new(AtomicBool::new(false));
+        let limits = SearchLimits::with_depth(4);
+
+        let options = SearchParams::default().options();
+        let mut all_minimal = Search::new();
+        for &(name, _, min, _) in &options {
+            let mut search = Search::new();
+            search.set_param(name, &min.to_string()).unwrap();
+            assert!(search
+                .find_best_move(&mut board, &limits, &stop)
+                .best_move
+                .is_some());
+            all_minimal.set_param(name, &min.to_string()).unwrap();
+        }
+        // With the other spin options at their minimums too.
+        all_minimal.set_hash_size(1);
+        all_minimal.set_threads(1);
+        all_minimal.set_multi_pv(1);
+        let limits = SearchLimits {
+            contempt: -crate::engine::MAX_CONTEMPT_CP,
+            skill: Skill::from_level(0, 1),
+            ..limits
+        };
+        assert!(all_minimal
+            .find_best_move(&mut board, &limits, &stop)
+            .best_move
+            .is_some());
+    }
+
+    #[test]
+    fn insufficient_material_needs_at_most_one_minor() {
+        let mut board = Board {
+            board: [Piece::Empty; 64],
+            white_to_move: true,
+            can_white_castle_kingside: false,
+            can_white_castle_queenside: false,
+            can_black_castle_kingside: false,
+            can_black_castle_queenside: false,
+            en_passant_square: -1,
+            half_move_capture_or_pawn_clock: 0,
+            full_move_number: 1,
+        };
+        board.board[4] = Piece::BK;
+        board.board[60] = Piece::WK;
+        assert!(insufficient_material(&board));
+        board.board[20] = Piece::WB;
+        assert!(insufficient_material(&board));
+        board.board[21] = Piece::BN;
+        assert!(!insufficient_material(&board));
+        board.board[21] = Piece::BP;
+        assert!(!insufficient_material(&board));
+    }
+
+    #[test]
+    fn null_move_restores_position_and_skips_pawn_endings() {
+        let mut board = Board {
+            board: [Piece::Empty; 64],
+            white_to_move: false,
+            can_white_castle_kingside: false,
+            can_white_castle_queenside: false,
+            can_black_castle_kingside: false,
+            can_black_castle_queenside: false,
+            en_passant_square: -1,
+            half_move_capture_or_pawn_clock: 0,
+            full_move_number: 1,
+        };
+        let params = PositionParams {
+            is_fen: true,
+            position: "4k3/8/8/8/3pP3/8/8/4KN2 b - e3 0 1".to_string(),
+            moves: vec![],
+        };
+        set_board_position(&mut board, &params).unwrap();
+        assert!(has_non_pawn_material(&board, true));
+        assert!(!has_non_pawn_material(&board, false));
+
+        let key = board.compute_zobrist_key();
+        board.half_move_capture_or_pawn_clock = 7;
+        let null_undo = make_null_move(&mut board);
+        assert!(board.white_to_move);
+        assert_eq!(board.en_passant_square, -1);
+        assert_eq!(board.half_move_capture_or_pawn_clock, 0);
+        assert_ne!(board.compute_zobrist_key(), key);
+        unmake_null_move(&mut board, null_undo);
+        assert_eq!(board.half_move_capture_or_pawn_clock, 7);
+        assert_eq!(board.compute_zobrist_key(), key);
     }
 }
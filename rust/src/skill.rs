@@ -0,0 +1,145 @@
+use crate::search::PvLine;
+
+pub const MAX_SKILL_LEVEL: i32 = 20;
+// Weakened searches look at this many lines to choose from.
+pub const SKILL_MULTI_PV: usize = 4;
+
+// Strength of each level in Elo, anchored at MAX_ELO for full strength.
+// Measured with `python selfplay.py <engine> <level> 60`, 60 games against
+// full strength at 20000 nodes a move. Provisional: 60 games leave about
+// 90 Elo of error either way, level 0 lost every game so its figure is
+// only a ceiling, and levels 12 and 16 both scored 19.5/60. Levels
+// between these are interpolated.
+const CALIBRATED_LEVELS: [(i32, i32); 6] = [
+    (0, 1170),
+    (4, 1490),
+    (8, 1780),
+    (12, 1870),
+    (16, 1870),
+    (MAX_SKILL_LEVEL, 2000),
+];
+pub const MIN_ELO: i32 = CALIBRATED_LEVELS[0].1;
+pub const MAX_ELO: i32 = CALIBRATED_LEVELS[CALIBRATED_LEVELS.len() - 1].1;
+
+// Scores are compared in centipawns, clamped so mates cannot overflow.
+const MAX_SCORE_CP: i64 = 10_000;
+
+/// Playing strength below full: a shallower, smaller search whose move is
+/// drawn from the best few lines, so mistakes are plausible moves rather
+/// than random ones. `seed` makes the choice reproducible.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Skill {
+    level: i32,
+    seed: u64,
+}
+
+impl Skill {
+    /// None at full strength.
+    pub fn from_level(level: i32, seed: u64) -> Option<Skill> {
+        (level < MAX_SKILL_LEVEL).then(|| Skill {
+            level: level.max(0),
+            seed,
+        })
+    }
+
+    /// The level whose calibrated strength is nearest `elo`.
+    pub fn level_for_elo(elo: i32) -> i32 {
+        let elo = elo.clamp(MIN_ELO, MAX_ELO);
+        let upper = CALIBRATED_LEVELS
+            .iter()
+            .position(|&(_, level_elo)| level_elo >= elo)
+            .unwrap();
+        if upper == 0 {
+            return CALIBRATED_LEVELS[0].0;
+        }
+        let (low_level, low_elo) = CALIBRATED_LEVELS[upper - 1];
+        let (high_level, high_elo) = CALIBRATED_LEVELS[upper];
+        let fraction = (elo - low_elo) as f64 / (high_elo - low_elo) as f64;
+        low_level + (fraction * (high_level - low_level) as f64).round() as i32
+    }
+
+    pub fn level(&self) -> i32 {
+        self.level
+    }
+
+    pub fn depth_limit(&self) -> i32 {
+        1 + self.level / 2
+    }
+
+    pub fn node_limit(&self) -> u64 {
+        let level = self.level as u64 + 1;
+        200 * level * level
+    }
+
+    /// Centipawns a line may trail the best one and still be played.
+    fn weakness(&self) -> i64 {
+        120 - 5 * self.level as i64
+    }
+
+    /// Index of the line to play: the best after adding each score a
+    /// random bonus below the weakness. Only near-best lines compete, and
+    /// weaker levels let them trail further.
+    pub fn pick(&self, lines: &[PvLine]) -> usize {
+        let mut state = self.seed;
+        let mut chosen = (0, i64::MIN);
+        for (i, line) in lines.iter().enumerate() {
+            let score = ((line.score * 100.0).round() as i64).clamp(-MAX_SCORE_CP, MAX_SCORE_CP);
+            let bonus = (next_random(&mut state) % self.weakness() as u64) as i64;
+            if score + bonus > chosen.1 {
+                chosen = (i, score + bonus);
+            }
+        }
+        chosen.0
+    }
+}
+
+/// splitmix64: advances `state` and returns the next value.
+pub fn next_random(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(scores: &[f64]) -> Vec<PvLine> {
+        scores
+            .iter()
+            .map(|&score| PvLine {
+                score,
+                moves: Vec::new(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_full_strength_has_no_skill() {
+        assert!(Skill::from_level(MAX_SKILL_LEVEL, 1).is_none());
+        assert_eq!(Skill::from_level(-3, 1).unwrap().level(), 0);
+        assert_eq!(Skill::level_for_elo(MAX_ELO), MAX_SKILL_LEVEL);
+        assert_eq!(Skill::level_for_elo(0), 0);
+        assert!(Skill::level_for_elo(1500) > Skill::level_for_elo(1200));
+    }
+
+    #[test]
+    fn test_pick_is_seeded_and_weakness_spreads_choices() {
+        let close = lines(&[0.5, 0.3, 0.0, -0.5]);
+        let picks = |level| {
+            (0..200)
+                .map(|seed| Skill::from_level(level, seed).unwrap().pick(&close))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(picks(5), picks(5));
+
+        let best_share = |level| picks(level).iter().filter(|&&i| i == 0).count();
+        assert!(best_share(19) > best_share(0));
+        assert!(picks(0).iter().any(|&i| i != 0));
+        // A line more than the weakness behind is never played.
+        let lost = lines(&[1.0, -0.5]);
+        assert!((0..50).all(|seed| Skill::from_level(0, seed).unwrap().pick(&lost) == 0));
+    }
+}
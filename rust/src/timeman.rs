@@ -0,0 +1,143 @@
+use std::time::Duration;
+
+use crate::chessmove::ChessMove;
+
+// Assumed number of moves left when the GUI sends no movestogo.
+const DEFAULT_MOVES_TO_GO: i32 = 30;
+const MAX_MOVES_TO_GO: i32 = 50;
+
+// Soft limit multiplier by how many iterations in a row kept the same best
+// move: unstable roots get more time, settled ones less.
+const STABILITY_SCALE: [f64; 6] = [1.6, 1.3, 1.1, 1.0, 0.85, 0.75];
+
+// Extra soft time per pawn the score fell since the previous iteration.
+const SCORE_DROP_SCALE: f64 = 1.0;
+const MAX_SCORE_DROP_FACTOR: f64 = 2.0;
+
+#[derive(Clone, Debug)]
+pub struct TimeManager {
+    soft_limit: Duration,
+    hard_limit: Duration,
+    last_best_move: Option<ChessMove>,
+    last_score: Option<f64>,
+    stability: usize,
+    drop_factor: f64,
+    scale: f64,
+}
+
+impl TimeManager {
+    /// Budgets one move from the side's clock. The soft limit is checked
+    /// between iterations, the hard limit aborts the search mid-iteration.
+    pub fn from_clock(
+        remaining: Duration,
+        increment: Duration,
+        moves_to_go: Option<i32>,
+        move_overhead: Duration,
+    ) -> Self {
+        let available = remaining.saturating_sub(move_overhead);
+        let moves_to_go = moves_to_go
+            .unwrap_or(DEFAULT_MOVES_TO_GO)
+            .clamp(1, MAX_MOVES_TO_GO) as u32;
+
+        let base = available / moves_to_go + increment.mul_f64(0.75);
+        let soft_limit = base.min(available.mul_f64(0.5));
+        let hard_limit = (soft_limit * 3).min(available.mul_f64(0.8));
+
+        TimeManager {
+            soft_limit,
+            hard_limit,
+            last_best_move: None,
+            last_score: None,
+            stability: 0,
+            drop_factor: 1.0,
+            scale: 1.0,
+        }
+    }
+
+    pub fn soft_limit(&self) -> Duration {
+        self.soft_limit
+    }
+
+    pub fn hard_limit(&self) -> Duration {
+        self.hard_limit
+    }
+
+    /// Feeds the result of a completed iteration into the soft limit scale.
+    pub fn update(&mut self, best_move: ChessMove, score: f64) {
+        if self.last_best_move == Some(best_move) {
+            self.stability += 1;
+        } else {
+            self.stability = 0;
+        }
+
+        let drop = self.last_score.map_or(0.0, |last| last - score);
+        self.drop_factor = (1.0 + drop * SCORE_DROP_SCALE).clamp(1.0, MAX_SCORE_DROP_FACTOR);
+
+        self.scale =
+            STABILITY_SCALE[self.stability.min(STABILITY_SCALE.len() - 1)] * self.drop_factor;
+        self.last_best_move = Some(best_move);
+        self.last_score = Some(score);
+    }
+
+    /// Whether the last iteration's score fell enough to extend the budget.
+    pub fn score_dropped(&self) -> bool {
+        self.drop_factor > 1.0
+    }
+
+    /// Whether another iteration should not be started.
+    pub fn should_stop(&self, elapsed: Duration) -> bool {
+        elapsed >= self.soft_limit.mul_f64(self.scale).min(self.hard_limit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limits_stay_within_clock() {
+        let tm = TimeManager::from_clock(
+            Duration::from_millis(60_000),
+            Duration::from_millis(1_000),
+            None,
+            Duration::from_millis(10),
+        );
+        assert!(tm.soft_limit() <= tm.hard_limit());
+        assert!(tm.hard_limit() < Duration::from_millis(60_000));
+        assert_eq!(tm.soft_limit().as_millis(), 2_749);
+
+        let sudden_death = TimeManager::from_clock(
+            Duration::from_millis(100),
+            Duration::ZERO,
+            Some(1),
+            Duration::from_millis(200),
+        );
+        assert_eq!(sudden_death.hard_limit(), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_stability_and_score_drop_scale_soft_limit() {
+        let mut tm = TimeManager::from_clock(
+            Duration::from_millis(30_000),
+            Duration::ZERO,
+            Some(10),
+            Duration::ZERO,
+        );
+        let soft = tm.soft_limit();
+        let mv = ChessMove::quiet(52, 36);
+
+        for _ in 0..6 {
+            tm.update(mv, 0.2);
+        }
+        assert!(tm.should_stop(soft.mul_f64(0.8)));
+
+        assert!(!tm.score_dropped());
+        tm.update(mv, -0.8);
+        assert!(tm.score_dropped());
+        assert!(!tm.should_stop(soft.mul_f64(1.2)));
+
+        tm.update(ChessMove::quiet(51, 35), -0.8);
+        assert!(!tm.should_stop(soft.mul_f64(1.5)));
+        assert!(tm.should_stop(soft.mul_f64(1.6)));
+    }
+}
@@ -0,0 +1,363 @@
+use std::fmt::Write;
+
+use crate::chessmove::ChessMove;
+use crate::search::{move_to_uci, MAX_PLY};
+use crate::tt::NodeType;
+
+// Windows narrower than this are labelled as zero windows.
+const ZERO_WINDOW: f64 = 0.001;
+
+/// Why a node returned without searching its moves, or why a move was
+/// skipped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Decision {
+    Draw,
+    MateDistance,
+    TtCutoff,
+    ReverseFutility,
+    Razoring,
+    NullMove,
+    ProbCut,
+    MultiCut,
+    Futility,
+    LateMovePruning,
+}
+
+impl Decision {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Decision::Draw => "draw",
+            Decision::MateDistance => "mate distance",
+            Decision::TtCutoff => "tt cutoff",
+            Decision::ReverseFutility => "reverse futility",
+            Decision::Razoring => "razoring",
+            Decision::NullMove => "null move",
+            Decision::ProbCut => "probcut",
+            Decision::MultiCut => "multicut",
+            Decision::Futility => "futility",
+            Decision::LateMovePruning => "late move pruning",
+        }
+    }
+}
+
+/// One visit to a node. Windows and scores are from the side to move.
+#[derive(Clone, Debug)]
+pub struct TraceNode {
+    pub parent: Option<usize>,
+    pub ply: usize,
+    // The move leading here; None at the root, for a null move, and for
+    // the reduced searches of the parent's own position that verify
+    // null-move cutoffs and singular moves.
+    pub mv: Option<ChessMove>,
+    pub depth: i32,
+    pub alpha: f64,
+    pub beta: f64,
+    // None for a pruned move, which was never searched.
+    pub score: Option<f64>,
+    pub decision: Option<Decision>,
+}
+
+impl TraceNode {
+    /// Where the score fell against the window.
+    pub fn node_type(&self) -> Option<NodeType> {
+        self.score.map(|score| {
+            if score <= self.alpha {
+                NodeType::AllNode
+            } else if score >= self.beta {
+                NodeType::CutNode
+            } else {
+                NodeType::PVNode
+            }
+        })
+    }
+
+    fn kind(&self, nodes: &[TraceNode]) -> String {
+        match (self.mv, self.parent.map(|p| nodes[p].ply)) {
+            (Some(mv), _) => move_to_uci(&mv),
+            (None, None) => "root".to_string(),
+            (None, Some(parent_ply)) if parent_ply == self.ply => "verify".to_string(),
+            (None, Some(_)) => "null".to_string(),
+        }
+    }
+}
+
+/// The tree of one completed iteration, in visiting order.
+#[derive(Clone, Debug, Default)]
+pub struct SearchTrace {
+    pub depth: i32,
+    pub nodes: Vec<TraceNode>,
+}
+
+impl SearchTrace {
+    /// A Graphviz digraph: pruned moves are dashed, fail-highs red and
+    /// exact scores bold.
+    pub fn to_dot(&self) -> String {
+        let mut dot = format!("digraph search {{\n  label=\"depth {}\";\n", self.depth);
+        dot.push_str("  node [shape=box, fontname=monospace];\n");
+        for (id, node) in self.nodes.iter().enumerate() {
+            // A zero window is narrower than the printed precision.
+            let window = if node.beta - node.alpha < ZERO_WINDOW {
+                format!("null window at {}", format_score(node.beta))
+            } else {
+                format!(
+                    "[{}, {}]",
+                    format_score(node.alpha),
+                    format_score(node.beta)
+                )
+            };
+            let mut label = format!("{}\\nd={} {}", node.kind(&self.nodes), node.depth, window);
+            if let Some(score) = node.score {
+                write!(label, "\\nscore {}", format_score(score)).unwrap();
+            }
+            if let Some(decision) = node.decision {
+                write!(label, "\\n{}", decision.name()).unwrap();
+            }
+            let style = match node.node_type() {
+                None => ", style=dashed, color=gray",
+                Some(NodeType::CutNode) => ", color=red",
+                Some(NodeType::PVNode) => ", style=bold",
+                Some(NodeType::AllNode) => "",
+            };
+            writeln!(dot, "  n{} [label=\"{}\"{}];", id, label, style).unwrap();
+            if let Some(parent) = node.parent {
+                writeln!(dot, "  n{} -> n{};", parent, id).unwrap();
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// `{"depth": d, "nodes": [...]}`, nodes indexed by position. Infinite
+    /// bounds are null.
+    pub fn to_json(&self) -> String {
+        let mut json = format!("{{\"depth\":{},\"nodes\":[", self.depth);
+        for (id, node) in self.nodes.iter().enumerate() {
+            if id > 0 {
+                json.push(',');
+            }
+            let node_type = match node.node_type() {
+                Some(NodeType::PVNode) => "\"exact\"",
+                Some(NodeType::AllNode) => "\"upper\"",
+                Some(NodeType::CutNode) => "\"lower\"",
+                None => "null",
+            };
+            write!(
+                json,
+                "{{\"id\":{},\"parent\":{},\"ply\":{},\"move\":{},\"depth\":{},\
+                 \"alpha\":{},\"beta\":{},\"score\":{},\"type\":{},\"decision\":{}}}",
+                id,
+                node.parent.map_or("null".to_string(), |p| p.to_string()),
+                node.ply,
+                node.mv
+                    .map_or("null".to_string(), |mv| format!("\"{}\"", move_to_uci(&mv))),
+                node.depth,
+                json_number(node.alpha),
+                json_number(node.beta),
+                node.score.map_or("null".to_string(), json_number),
+                node_type,
+                node.decision
+                    .map_or("null".to_string(), |d| format!("\"{}\"", d.name())),
+            )
+            .unwrap();
+        }
+        json.push_str("]}\n");
+        json
+    }
+}
+
+fn format_score(score: f64) -> String {
+    if score.abs() < 0.005 {
+        // Not "-0.00".
+        "0.00".to_string()
+    } else if score.is_finite() {
+        format!("{:.2}", score)
+    } else if score > 0.0 {
+        "inf".to_string()
+    } else {
+        "-inf".to_string()
+    }
+}
+
+fn json_number(value: f64) -> String {
+    if value.is_finite() {
+        value.to_string()
+    } else {
+        "null".to_string()
+    }
+}
+
+/// Builds a [`SearchTrace`] as the search runs. Nodes deeper than
+/// `max_ply` are searched but not recorded.
+#[derive(Clone, Debug)]
+pub struct Tracer {
+    max_ply: usize,
+    nodes: Vec<TraceNode>,
+    // Recorded nodes currently being searched, innermost last.
+    stack: Vec<usize>,
+    // Nodes entered beyond `max_ply` and not yet left.
+    hidden: usize,
+    // The move made at each ply of the current line.
+    line: [Option<ChessMove>; MAX_PLY],
+    completed: Option<SearchTrace>,
+}
+
+impl Tracer {
+    pub fn new(max_ply: usize) -> Self {
+        Tracer {
+            max_ply,
+            nodes: Vec::new(),
+            stack: Vec::new(),
+            hidden: 0,
+            line: [None; MAX_PLY],
+            completed: None,
+        }
+    }
+
+    /// Drops the tree so far, including any completed one.
+    pub fn reset(&mut self) {
+        self.start_iteration();
+        self.completed = None;
+    }
+
+    /// Starts a new tree; a stopped iteration's nodes are thrown away.
+    pub fn start_iteration(&mut self) {
+        self.nodes.clear();
+        self.stack.clear();
+        self.hidden = 0;
+    }
+
+    pub fn finish_iteration(&mut self, depth: i32) {
+        self.completed = Some(SearchTrace {
+            depth,
+            nodes: std::mem::take(&mut self.nodes),
+        });
+        self.stack.clear();
+    }
+
+    /// The tree of the last completed iteration.
+    pub fn take_completed(&mut self) -> Option<SearchTrace> {
+        self.completed.take()
+    }
+
+    /// Records the move made at `ply`; None for a null move.
+    pub fn set_move(&mut self, ply: usize, mv: Option<ChessMove>) {
+        self.line[ply] = mv;
+    }
+
+    pub fn enter(&mut self, ply: usize, depth: i32, alpha: f64, beta: f64) {
+        if self.hidden > 0 || ply > self.max_ply {
+            self.hidden += 1;
+            return;
+        }
+        let parent = self.stack.last().copied();
+        let mv = match parent {
+            Some(p) if self.nodes[p].ply < ply => self.line[ply - 1],
+            _ => None,
+        };
+        self.stack.push(self.nodes.len());
+        self.nodes.push(TraceNode {
+            parent,
+            ply,
+            mv,
+            depth,
+            alpha,
+            beta,
+            score: None,
+            decision: None,
+        });
+    }
+
+    /// Notes why the current node is returning early.
+    pub fn decide(&mut self, decision: Decision) {
+        if self.hidden == 0 {
+            if let Some(&id) = self.stack.last() {
+                self.nodes[id].decision = Some(decision);
+            }
+        }
+    }
+
+    /// Records `mv`, skipped at the current node, as an unsearched child.
+    pub fn prune(&mut self, mv: ChessMove, depth: i32, alpha: f64, beta: f64, decision: Decision) {
+        let Some(&parent) = self.stack.last().filter(|_| self.hidden == 0) else {
+            return;
+        };
+        let ply = self.nodes[parent].ply + 1;
+        if ply > self.max_ply {
+            return;
+        }
+        self.nodes.push(TraceNode {
+            parent: Some(parent),
+            ply,
+            mv: Some(mv),
+            depth,
+            alpha: -beta,
+            beta: -alpha,
+            score: None,
+            decision: Some(decision),
+        });
+    }
+
+    pub fn exit(&mut self, score: f64) {
+        if self.hidden > 0 {
+            self.hidden -= 1;
+            return;
+        }
+        if let Some(id) = self.stack.pop() {
+            self.nodes[id].score = Some(score);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tracer_builds_tree_to_max_ply() {
+        let mut tracer = Tracer::new(1);
+        tracer.start_iteration();
+        tracer.enter(0, 2, f64::NEG_INFINITY, f64::INFINITY);
+        tracer.set_move(0, Some(ChessMove::quiet(52, 36)));
+        tracer.enter(1, 1, f64::NEG_INFINITY, f64::INFINITY);
+        tracer.set_move(1, Some(ChessMove::quiet(12, 28)));
+        tracer.enter(2, 0, f64::NEG_INFINITY, f64::INFINITY);
+        tracer.decide(Decision::TtCutoff);
+        tracer.exit(0.1);
+        tracer.exit(-0.1);
+        tracer.prune(ChessMove::quiet(48, 40), 1, 0.1, 0.2, Decision::Futility);
+        tracer.enter(1, 0, -0.1, -0.0999);
+        tracer.exit(-0.1);
+        tracer.exit(0.1);
+        // An unfinished iteration does not replace the completed tree.
+        tracer.finish_iteration(2);
+        tracer.start_iteration();
+        tracer.enter(0, 3, 0.0, 1.0);
+
+        let trace = tracer.take_completed().unwrap();
+        assert_eq!(trace.depth, 2);
+        assert_eq!(trace.nodes.len(), 4);
+        let child = &trace.nodes[1];
+        assert_eq!(
+            (child.parent, child.ply, child.score),
+            (Some(0), 1, Some(-0.1))
+        );
+        assert_eq!(move_to_uci(&child.mv.unwrap()), "e2e4");
+        assert_eq!(child.decision, None);
+        let pruned = &trace.nodes[2];
+        assert_eq!(
+            (pruned.alpha, pruned.beta, pruned.node_type()),
+            (-0.2, -0.1, None)
+        );
+
+        let dot = trace.to_dot();
+        assert!(dot.starts_with("digraph search {"));
+        assert!(dot.contains("n0 -> n1;") && dot.contains("n0 -> n2;"));
+        assert!(dot.contains("a2a3\\nd=1 [-0.20, -0.10]\\nfutility\", style=dashed"));
+        assert!(dot.contains("d=0 null window at -0.10\\nscore -0.10\"];"));
+
+        let json = trace.to_json();
+        assert!(json.starts_with("{\"depth\":2,\"nodes\":[{\"id\":0,\"parent\":null"));
+        assert!(json.contains("\"alpha\":null,\"beta\":null,\"score\":0.1,\"type\":\"exact\""));
+        assert!(json.contains("\"move\":\"a2a3\""));
+    }
+}
@@ -0,0 +1,311 @@
+use std::mem::size_of;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+use crate::board::ZobristKey;
+use crate::chessmove::ChessMove;
+use crate::search::{MATE_BOUND, MATE_SCORE, MAX_PLY};
+
+pub const DEFAULT_HASH_MB: usize = 16;
+pub const MAX_HASH_MB: usize = 4096;
+
+const BUCKET_SIZE: usize = 4;
+// hashfull samples this many buckets, i.e. 1000 slots.
+const HASHFULL_SAMPLE: usize = 1000 / BUCKET_SIZE;
+
+const GENERATION_BITS: u32 = 6;
+const GENERATION_MASK: u8 = (1 << GENERATION_BITS) - 1;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NodeType {
+    PVNode,  // Exact
+    AllNode, // Alpha
+    CutNode, // Beta
+}
+
+/// A hash move as from/to/promotion; compare with [`pack_move`] of the
+/// generated moves to recover the full move.
+pub type PackedMove = u16;
+
+#[derive(Clone, Debug)]
+pub struct TranspositionTableEntry {
+    pub depth: i32,
+    pub value: f64,
+    pub node_type: NodeType,
+    pub best_move: PackedMove,
+}
+
+pub fn pack_move(mv: &ChessMove) -> PackedMove {
+    (mv.from as u16) | (mv.to as u16) << 6 | (mv.promoted_piece as u16) << 12
+}
+
+// Values are stored in centipawns. Mate scores, MATE_SCORE less a whole
+// number of plies, take the ends of the range so both fit in 16 bits.
+const MATE_VALUE: i32 = i16::MAX as i32;
+const MAX_VALUE_CP: i32 = MATE_VALUE - MAX_PLY as i32 - 1;
+
+fn pack_value(value: f64) -> u16 {
+    let stored = if value.abs() >= MATE_BOUND {
+        // Infinite bounds are kept as mate now.
+        let plies = (MATE_SCORE - value.abs()).max(0.0).round() as i32;
+        (MATE_VALUE - plies) * value.signum() as i32
+    } else {
+        ((value * 100.0).round() as i32).clamp(-MAX_VALUE_CP, MAX_VALUE_CP)
+    };
+    stored as i16 as u16
+}
+
+fn unpack_value(bits: u16) -> f64 {
+    let stored = bits as i16 as i32;
+    if stored.abs() > MAX_VALUE_CP {
+        (MATE_SCORE - (MATE_VALUE - stored.abs()) as f64) * stored.signum() as f64
+    } else {
+        stored as f64 / 100.0
+    }
+}
+
+// Slot layout, low to high: value (16 bits), move (16), depth (8), node
+// type (2, zero when empty), generation (6) and the low 16 bits of the
+// key (16), whose high bits already chose the bucket.
+fn pack_slot(
+    key: ZobristKey,
+    value: f64,
+    node_type: NodeType,
+    depth: i32,
+    best_move: PackedMove,
+    generation: u8,
+) -> u64 {
+    let bound = match node_type {
+        NodeType::PVNode => 1,
+        NodeType::AllNode => 2,
+        NodeType::CutNode => 3,
+    };
+    (pack_value(value) as u64)
+        | (best_move as u64) << 16
+        | (depth.clamp(i8::MIN as i32, i8::MAX as i32) as i8 as u8 as u64) << 32
+        | bound << 40
+        | ((generation & GENERATION_MASK) as u64) << 42
+        | (key as u16 as u64) << 48
+}
+
+fn slot_bound(slot: u64) -> u64 {
+    (slot >> 40) & 3
+}
+
+fn slot_depth(slot: u64) -> i32 {
+    (slot >> 32) as u8 as i8 as i32
+}
+
+fn slot_generation(slot: u64) -> u8 {
+    (slot >> 42) as u8 & GENERATION_MASK
+}
+
+fn is_empty(slot: u64) -> bool {
+    slot_bound(slot) == 0
+}
+
+/// Whether `slot` holds an entry for `key`. Only 16 bits are compared, so
+/// another position's entry matches about once in 16384 probes; callers
+/// check the hash move against the legal moves.
+fn holds(slot: u64, key: ZobristKey) -> bool {
+    !is_empty(slot) && (slot >> 48) as u16 == key as u16
+}
+
+fn unpack_slot(slot: u64) -> TranspositionTableEntry {
+    TranspositionTableEntry {
+        depth: slot_depth(slot),
+        value: unpack_value(slot as u16),
+        node_type: match slot_bound(slot) {
+            1 => NodeType::PVNode,
+            2 => NodeType::AllNode,
+            _ => NodeType::CutNode,
+        },
+        best_move: (slot >> 16) as u16,
+    }
+}
+
+// Each slot is a single word, so threads share the table without locks
+// and never read half of another thread's write.
+type Bucket = [AtomicU64; BUCKET_SIZE];
+
+/// Fixed-size, bucketed table shared by all search threads. Within a
+/// bucket, shallow entries from old searches are replaced first.
+pub struct TranspositionTable {
+    buckets: Vec<Bucket>,
+    generation: AtomicU8,
+}
+
+impl TranspositionTable {
+    pub fn new(size_mb: usize) -> Self {
+        let bucket_count = (size_mb.clamp(1, MAX_HASH_MB) << 20) / size_of::<Bucket>();
+        TranspositionTable {
+            buckets: (0..bucket_count.max(1))
+                .map(|_| Bucket::default())
+                .collect(),
+            generation: AtomicU8::new(0),
+        }
+    }
+
+    fn bucket_index(&self, key: ZobristKey) -> usize {
+        ((key as u128 * self.buckets.len() as u128) >> 64) as usize
+    }
+
+    fn generation(&self) -> u8 {
+        self.generation.load(Ordering::Relaxed) & GENERATION_MASK
+    }
+
+    /// Marks the start of a new search so older entries age out.
+    pub fn new_search(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn probe(&self, key: ZobristKey) -> Option<TranspositionTableEntry> {
+        self.buckets[self.bucket_index(key)]
+            .iter()
+            .map(|slot| slot.load(Ordering::Relaxed))
+            .find(|&slot| holds(slot, key))
+            .map(unpack_slot)
+    }
+
+    pub fn store(
+        &self,
+        key: ZobristKey,
+        value: f64,
+        node_type: NodeType,
+        depth: i32,
+        best_move: &ChessMove,
+    ) {
+        let generation = self.generation();
+        let bucket = &self.buckets[self.bucket_index(key)];
+        let slots: [u64; BUCKET_SIZE] = core::array::from_fn(|i| bucket[i].load(Ordering::Relaxed));
+
+        let victim = slots
+            .iter()
+            .position(|&slot| is_empty(slot) || holds(slot, key))
+            .unwrap_or_else(|| {
+                // Each search of age costs as much as eight plies of depth.
+                let worth = |slot: u64| {
+                    let age = generation.wrapping_sub(slot_generation(slot)) & GENERATION_MASK;
+                    slot_depth(slot) - 8 * age as i32
+                };
+                (0..BUCKET_SIZE).min_by_key(|&i| worth(slots[i])).unwrap()
+            });
+
+        let slot = pack_slot(
+            key,
+            value,
+            node_type,
+            depth,
+            pack_move(best_move),
+            generation,
+        );
+        bucket[victim].store(slot, Ordering::Relaxed);
+    }
+
+    /// Permille of sampled slots written during the current search.
+    pub fn hashfull(&self) -> i32 {
+        let generation = self.generation();
+        let sample = HASHFULL_SAMPLE.min(self.buckets.len());
+        let used = self.buckets[..sample]
+            .iter()
+            .flatten()
+            .map(|slot| slot.load(Ordering::Relaxed))
+            .filter(|&slot| !is_empty(slot) && slot_generation(slot) == generation)
+            .count();
+        (used * 1000 / (sample * BUCKET_SIZE)) as i32
+    }
+
+    pub fn clear(&self) {
+        for slot in self.buckets.iter().flatten() {
+            slot.store(0, Ordering::Relaxed);
+        }
+        self.generation.store(0, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_store_and_probe() {
+        let tt = TranspositionTable::new(1);
+        let key = 0x1234_5678_9abc_def0;
+        assert!(tt.probe(key).is_none());
+
+        tt.store(key, -1.5, NodeType::CutNode, 4, &ChessMove::quiet(52, 36));
+        let entry = tt.probe(key).unwrap();
+        assert_eq!(entry.depth, 4);
+        assert_eq!(entry.value, -1.5);
+        assert_eq!(entry.node_type, NodeType::CutNode);
+        assert_eq!(entry.best_move, pack_move(&ChessMove::quiet(52, 36)));
+
+        tt.clear();
+        assert!(tt.probe(key).is_none());
+    }
+
+    #[test]
+    fn test_replacement_prefers_deep_and_recent_entries() {
+        let tt = TranspositionTable::new(1);
+        // Same high bits, so all keys share one bucket.
+        let keys: Vec<ZobristKey> = (1..=6).map(|i| 0xabcd_0000_0000_0000 | i).collect();
+
+        for (i, &key) in keys[..BUCKET_SIZE].iter().enumerate() {
+            tt.store(
+                key,
+                0.0,
+                NodeType::PVNode,
+                10 + i as i32,
+                &ChessMove::quiet(0, 1),
+            );
+        }
+        tt.store(keys[4], 0.0, NodeType::PVNode, 1, &ChessMove::quiet(0, 1));
+        // The shallowest entry made room.
+        assert!(tt.probe(keys[0]).is_none());
+        assert!(tt.probe(keys[1]).is_some());
+        assert!(tt.probe(keys[4]).is_some());
+
+        tt.new_search();
+        tt.new_search();
+        tt.store(keys[5], 0.0, NodeType::PVNode, 1, &ChessMove::quiet(0, 1));
+        tt.store(keys[0], 0.0, NodeType::PVNode, 1, &ChessMove::quiet(0, 1));
+        // Two searches of age outweigh ten plies of depth.
+        assert!(tt.probe(keys[1]).is_none());
+        assert!(tt.probe(keys[5]).is_some());
+        assert!(tt.probe(keys[2]).is_some());
+    }
+
+    #[test]
+    fn test_hashfull_counts_current_generation() {
+        let tt = TranspositionTable::new(1);
+        assert_eq!(tt.hashfull(), 0);
+        for i in 0..(1u64 << 16) {
+            let key = i.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+            tt.store(key, 0.0, NodeType::AllNode, 1, &ChessMove::quiet(0, 1));
+        }
+        assert!(tt.hashfull() > 0);
+        tt.new_search();
+        assert_eq!(tt.hashfull(), 0);
+    }
+
+    #[test]
+    fn test_slot_packs_key_check_and_mate_scores() {
+        assert_eq!(size_of::<Bucket>(), 8 * BUCKET_SIZE);
+        let tt = TranspositionTable::new(1);
+        let key = 0x0f0f_0f0f_0f0f_0f0f;
+        for value in [1.23, -0.05, MATE_SCORE - 5.0, -(MATE_SCORE - 3.0)] {
+            tt.store(key, value, NodeType::PVNode, 3, &ChessMove::quiet(12, 28));
+            assert_eq!(tt.probe(key).unwrap().value, value);
+        }
+        tt.store(
+            key,
+            f64::INFINITY,
+            NodeType::CutNode,
+            3,
+            &ChessMove::quiet(12, 28),
+        );
+        assert_eq!(tt.probe(key).unwrap().value, MATE_SCORE);
+
+        // Same bucket, different low bits.
+        assert!(tt.probe(key ^ 1).is_none());
+    }
+}
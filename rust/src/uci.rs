@@ -1,10 +1,31 @@
 use std::io::{self, BufRead};
 use std::str::FromStr;
 
-use crate::engine::Engine;
-use crate::params::{GoParams, OptionParams, PositionParams, SearchInfo};
+use crate::engine::{
+    Engine, DEFAULT_BENCH_DEPTH, DEFAULT_MOVE_OVERHEAD_MS, DEFAULT_TRACE_DEPTH, DEFAULT_TRACE_FILE,
+    MAX_CONTEMPT_CP, MAX_THREADS, MAX_TRACE_DEPTH,
+};
+use crate::params::{GoParams, OptionParams, PositionParams, SearchInfo, SearchParams};
+use crate::search::{move_to_uci, SearchObserver, SearchResult};
+use crate::skill::{MAX_ELO, MAX_SKILL_LEVEL, MIN_ELO};
+use crate::tt::{DEFAULT_HASH_MB, MAX_HASH_MB};
 use std::time::Duration;
 
+const GO_KEYWORDS: [&str; 12] = [
+    "searchmoves",
+    "ponder",
+    "wtime",
+    "btime",
+    "winc",
+    "binc",
+    "movestogo",
+    "depth",
+    "nodes",
+    "mate",
+    "movetime",
+    "infinite",
+];
+
 pub struct UCI<'a> {
     running: bool,
     engine: &'a mut Engine,
@@ -23,10 +44,6 @@ impl<'a> UCI<'a> {
         let stdin = io::stdin();
 
         for line_result in stdin.lock().lines() {
-            if !self.running {
-                break;
-            }
-
             let line = match line_result {
                 Ok(s) => s.trim().to_string(),
                 Err(_) => break,
@@ -36,6 +53,9 @@ impl<'a> UCI<'a> {
             if !recognized {
                 eprintln!("Unknown command: {}", line);
             }
+            if !self.running {
+                break;
+            }
         }
     }
 
@@ -48,9 +68,8 @@ impl<'a> UCI<'a> {
             self.quit_command();
         } else if cmd == "ucinewgame" {
             self.uci_new_game_command();
-        } else if cmd.starts_with("debug") {
-            let on = cmd.contains("on");
-            self.debug_command(on);
+        } else if let Some(args) = cmd.strip_prefix("debug") {
+            self.debug_command(args.trim() == "on");
         } else if cmd.starts_with("setoption") {
             let option_params = self.parse_option_command(&cmd["setoption".len()..]);
             self.set_option_command(&option_params.name, &option_params.value);
@@ -66,6 +85,8 @@ impl<'a> UCI<'a> {
             self.stop_command();
         } else if cmd == "ponderhit" {
             self.ponder_hit_command();
+        } else if let Some(args) = cmd.strip_prefix("bench") {
+            self.bench_command(args);
         } else {
             return false;
         }
@@ -75,6 +96,70 @@ impl<'a> UCI<'a> {
     fn uci_command(&self) {
         println!("id name Kaissa");
         println!("id author kw");
+        self.send_option(
+            "Hash",
+            "spin",
+            Some(&DEFAULT_HASH_MB.to_string()),
+            Some("1"),
+            Some(&MAX_HASH_MB.to_string()),
+        );
+        self.send_option("Clear Hash", "button", None, None, None);
+        self.send_option(
+            "Threads",
+            "spin",
+            Some("1"),
+            Some("1"),
+            Some(&MAX_THREADS.to_string()),
+        );
+        self.send_option("Ponder", "check", Some("false"), None, None);
+        self.send_option("MultiPV", "spin", Some("1"), Some("1"), Some("256"));
+        self.send_option(
+            "Move Overhead",
+            "spin",
+            Some(&DEFAULT_MOVE_OVERHEAD_MS.to_string()),
+            Some("0"),
+            Some("5000"),
+        );
+        self.send_option(
+            "Contempt",
+            "spin",
+            Some("0"),
+            Some(&(-MAX_CONTEMPT_CP).to_string()),
+            Some(&MAX_CONTEMPT_CP.to_string()),
+        );
+        self.send_option("UCI_AnalyseMode", "check", Some("false"), None, None);
+        self.send_option(
+            "Skill Level",
+            "spin",
+            Some(&MAX_SKILL_LEVEL.to_string()),
+            Some("0"),
+            Some(&MAX_SKILL_LEVEL.to_string()),
+        );
+        self.send_option("UCI_LimitStrength", "check", Some("false"), None, None);
+        self.send_option(
+            "UCI_Elo",
+            "spin",
+            Some(&MAX_ELO.to_string()),
+            Some(&MIN_ELO.to_string()),
+            Some(&MAX_ELO.to_string()),
+        );
+        self.send_option(
+            "Trace Depth",
+            "spin",
+            Some(&DEFAULT_TRACE_DEPTH.to_string()),
+            Some("1"),
+            Some(&MAX_TRACE_DEPTH.to_string()),
+        );
+        self.send_option("Trace File", "string", Some(DEFAULT_TRACE_FILE), None, None);
+        for (name, value, min, max) in SearchParams::default().options() {
+            self.send_option(
+                name,
+                "spin",
+                Some(&value.to_string()),
+                Some(&min.to_string()),
+                Some(&max.to_string()),
+            );
+        }
         println!("uciok");
     }
 
@@ -83,31 +168,50 @@ impl<'a> UCI<'a> {
     }
 
     fn quit_command(&mut self) {
+        self.engine.stop();
         self.running = false;
     }
 
-    fn uci_new_game_command(&self) {
-        println!("ucinewgame unsupported");
+    fn uci_new_game_command(&mut self) {
+        self.engine.new_game();
     }
 
-    fn debug_command(&self, on: bool) {
-        println!("debug {}unsupported", if on { "on " } else { "off " });
+    fn debug_command(&mut self, on: bool) {
+        self.engine.set_debug(on);
     }
 
     fn stop_command(&mut self) {
         self.engine.stop();
     }
 
-    fn ponder_hit_command(&self) {
-        println!("ponder unsupported");
+    fn ponder_hit_command(&mut self) {
+        self.engine.ponder_hit();
+    }
+
+    /// `bench [depth]`, not part of UCI.
+    fn bench_command(&mut self, args: &str) {
+        let depth = match args.trim() {
+            "" => DEFAULT_BENCH_DEPTH,
+            depth => match depth.parse::<i32>() {
+                Ok(depth) if depth > 0 => depth,
+                _ => {
+                    println!("info string Invalid bench depth '{}'", depth);
+                    return;
+                }
+            },
+        };
+        let (nodes, time) = self.engine.bench(depth);
+        UCI::send_bench_result(nodes, time);
     }
 
     fn register_command(&self, _params: &str) {
         println!("registration ok");
     }
 
-    fn set_option_command(&self, _name: &str, _value: &str) {
-        println!("no options available");
+    fn set_option_command(&mut self, name: &str, value: &str) {
+        if let Err(e) = self.engine.set_option(name, value) {
+            println!("info string {}", e);
+        }
     }
 
     fn position_command(&mut self, params: PositionParams) {
@@ -173,6 +277,17 @@ impl<'a> UCI<'a> {
         let mut i = 0;
         while i < tokens.len() {
             match tokens[i] {
+                "searchmoves" => {
+                    i += 1;
+                    while i < tokens.len() && !GO_KEYWORDS.contains(&tokens[i]) {
+                        params.searchmoves.push(tokens[i].to_string());
+                        i += 1;
+                    }
+                }
+                "ponder" => {
+                    params.ponder = Some(true);
+                    i += 1;
+                }
                 "infinite" => {
                     params.infinite = true;
                     i += 1;
@@ -234,7 +349,7 @@ impl<'a> UCI<'a> {
                 "nodes" => {
                     i += 1;
                     if i < tokens.len() {
-                        if let Ok(n) = i32::from_str(tokens[i]) {
+                        if let Ok(n) = tokens[i].parse::<u64>() {
                             params.nodes = Some(n);
                         }
                         i += 1;
@@ -269,36 +384,29 @@ impl<'a> UCI<'a> {
 
     fn parse_option_command(&self, args: &str) -> OptionParams {
         let tokens: Vec<&str> = args.split_whitespace().collect();
-        let mut name = String::new();
-        let mut value = String::new();
-
-        let mut i = 0;
-        while i < tokens.len() {
-            match tokens[i] {
-                "name" => {
-                    i += 1;
-                    if i < tokens.len() {
-                        name = tokens[i].to_string();
-                    }
-                    i += 1;
-                }
-                "value" => {
-                    i += 1;
-                    if i < tokens.len() {
-                        value = tokens[i].to_string();
-                    }
-                    i += 1;
-                }
+        let mut name_tokens: Vec<&str> = Vec::new();
+        let mut value_tokens: Vec<&str> = Vec::new();
+
+        // Names and values may contain spaces, e.g. "Move Overhead".
+        let mut target: Option<&mut Vec<&str>> = None;
+        for token in tokens {
+            match token {
+                "name" => target = Some(&mut name_tokens),
+                "value" => target = Some(&mut value_tokens),
                 _ => {
-                    i += 1;
+                    if let Some(t) = target.as_mut() {
+                        t.push(token);
+                    }
                 }
             }
         }
 
-        OptionParams { name, value }
+        OptionParams {
+            name: name_tokens.join(" "),
+            value: value_tokens.join(" "),
+        }
     }
 
-    #[allow(dead_code)]
     fn send_option(
         &self,
         name: &str,
@@ -320,8 +428,7 @@ impl<'a> UCI<'a> {
         println!();
     }
 
-    #[allow(dead_code)]
-    fn send_best_move(&self, mv: &str, ponder: Option<&str>) {
+    pub fn send_best_move(mv: &str, ponder: Option<&str>) {
         if let Some(pond) = ponder {
             println!("bestmove {} ponder {}", mv, pond);
         } else {
@@ -329,8 +436,18 @@ impl<'a> UCI<'a> {
         }
     }
 
-    #[allow(dead_code)]
-    fn send_info(&self, info: &SearchInfo) {
+    pub fn send_bench_result(nodes: u64, time: Duration) {
+        let nps = (nodes as f64 / time.as_secs_f64().max(0.001)) as u64;
+        println!("Total time (ms) : {}", time.as_millis());
+        println!("Nodes searched  : {}", nodes);
+        println!("Nodes/second    : {}", nps);
+    }
+
+    pub fn send_info_string(message: &str) {
+        println!("info string {}", message);
+    }
+
+    pub fn send_info(info: &SearchInfo) {
         print!("info");
         if let Some(d) = info.depth {
             print!(" depth {}", d);
@@ -338,21 +455,58 @@ impl<'a> UCI<'a> {
         if let Some(sd) = info.seldepth {
             print!(" seldepth {}", sd);
         }
+        if let Some(k) = info.multipv {
+            print!(" multipv {}", k);
+        }
         if let Some(t) = info.time {
             print!(" time {}", t.as_millis());
         }
         if let Some(n) = info.nodes {
             print!(" nodes {}", n);
         }
+        if let Some(nps) = info.nps {
+            print!(" nps {}", nps);
+        }
+        if let Some(hashfull) = info.hashfull {
+            print!(" hashfull {}", hashfull);
+        }
         if let Some(cp) = info.score_cp {
             print!(" score cp {}", cp);
         }
         if let Some(mate) = info.score_mate {
             print!(" score mate {}", mate);
         }
+        if info.lowerbound {
+            print!(" lowerbound");
+        } else if info.upperbound {
+            print!(" upperbound");
+        }
         if let Some(pv) = info.pv {
             print!(" pv {}", pv);
         }
         println!();
     }
 }
+
+/// Prints search output as UCI `info` and `bestmove` lines.
+pub struct UciObserver;
+
+impl SearchObserver for UciObserver {
+    fn on_info(&self, info: &SearchInfo) {
+        UCI::send_info(info);
+    }
+
+    fn on_message(&self, message: &str) {
+        UCI::send_info_string(message);
+    }
+
+    fn on_result(&self, result: &SearchResult) {
+        match result.best_move {
+            Some(mv) => {
+                let ponder = result.ponder.map(|p| move_to_uci(&p));
+                UCI::send_best_move(&move_to_uci(&mv), ponder.as_deref());
+            }
+            None => UCI::send_best_move("0000", None),
+        }
+    }
+}
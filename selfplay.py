@@ -0,0 +1,116 @@
+import math
+import re
+import subprocess
+import sys
+
+# Games longer than this are adjudicated from the engines' scores.
+MAX_PLIES = 200
+# Pawns both engines must agree on to adjudicate a win.
+ADJUDICATE_SCORE = 5.0
+MATE_SCORE = 100.0
+
+class UCIEngine:
+    def __init__(self, engine_path, options):
+        self.process = subprocess.Popen(
+            engine_path,
+            universal_newlines=True,
+            stdin=subprocess.PIPE,
+            stdout=subprocess.PIPE,
+            bufsize=1
+        )
+        self._send_and_wait("uci", "uciok")
+        for name, value in options.items():
+            self._send(f"setoption name {name} value {value}")
+        self._send_and_wait("isready", "readyok")
+
+    def _send(self, command):
+        self.process.stdin.write(command + "\n")
+        self.process.stdin.flush()
+
+    def _send_and_wait(self, command, wait_for):
+        self._send(command)
+        while True:
+            line = self.process.stdout.readline().strip()
+            if wait_for in line:
+                break
+
+    def new_game(self):
+        self._send("ucinewgame")
+        self._send_and_wait("isready", "readyok")
+
+    def get_move(self, moves, nodes):
+        """Returns the move and the last score reported, in pawns."""
+        self._send("position startpos" + (" moves " + " ".join(moves) if moves else ""))
+        self._send(f"go nodes {nodes}")
+        score = 0.0
+        while True:
+            line = self.process.stdout.readline().strip()
+            match = re.search(r"multipv 1 .*score (cp|mate) (-?\d+) pv", line)
+            if match and "bound" not in line:
+                value = int(match.group(2))
+                if match.group(1) == "cp":
+                    score = value / 100
+                else:
+                    score = MATE_SCORE if value > 0 else -MATE_SCORE
+            if line.startswith("bestmove"):
+                return line.split()[1], score
+
+    def close(self):
+        self._send("quit")
+        self.process.wait()
+
+def play(white, black, nodes):
+    """Plays one game from the start position; returns White's points."""
+    moves = []
+    scores = [0.0, 0.0]
+    for ply in range(MAX_PLIES):
+        side = ply % 2
+        move, score = (white, black)[side].get_move(moves, nodes)
+        if move == "0000":
+            # No legal move: mated if the opponent saw the mate coming.
+            if scores[1 - side] >= MATE_SCORE:
+                return 0.0 if side == 0 else 1.0
+            return 0.5
+        scores[side] = score
+        moves.append(move)
+    if scores[0] >= ADJUDICATE_SCORE and scores[1] <= -ADJUDICATE_SCORE:
+        return 1.0
+    if scores[0] <= -ADJUDICATE_SCORE and scores[1] >= ADJUDICATE_SCORE:
+        return 0.0
+    return 0.5
+
+def elo_difference(score, games):
+    # A perfect or zero score has no finite Elo; count it as half a game off.
+    score = min(max(score, 0.5 / games), 1 - 0.5 / games)
+    return -400 * math.log10(1 / score - 1)
+
+def main():
+    if len(sys.argv) not in (4, 5):
+        print("Usage: python selfplay.py <path-to-engine> <skill-level> <games> [nodes]")
+        sys.exit(1)
+
+    engine_path, level, games = sys.argv[1], int(sys.argv[2]), int(sys.argv[3])
+    nodes = int(sys.argv[4]) if len(sys.argv) == 5 else 20000
+    full = UCIEngine(engine_path, {})
+    weak = UCIEngine(engine_path, {"Skill Level": level})
+
+    points = 0.0
+    try:
+        for game in range(games):
+            full.new_game()
+            weak.new_game()
+            # Alternate colours; points are counted for the weak engine.
+            if game % 2 == 0:
+                points += 1 - play(full, weak, nodes)
+            else:
+                points += play(weak, full, nodes)
+    finally:
+        full.close()
+        weak.close()
+
+    score = points / games
+    print(f"level {level}: {points}/{games} = {score:.3f}, "
+          f"elo {elo_difference(score, games):+.0f} against full strength")
+
+if __name__ == "__main__":
+    main()